@@ -1,3 +1,4 @@
+use chrono::{Local, NaiveDateTime, NaiveTime, TimeDelta, Timelike};
 use clap::{Parser, Subcommand};
 use serde::{Deserialize, Serialize};
 
@@ -12,14 +13,49 @@ pub enum Commands {
         after_help = "
 \x1b[1mExample usage:\x1b[0m
     kswitch set light
-    kswitch set dark"
+    kswitch set dark
+    kswitch set dark --for 2h
+    kswitch set light --until 18:00"
     )]
     Set {
         #[command(subcommand)]
         theme: Theme,
+        /// Hold the theme against the schedule for a while, e.g. `2h` or `1h30m`
+        #[arg(long = "for", value_name = "DURATION", value_parser = parse_for, global = true, conflicts_with = "until")]
+        duration: Option<NaiveDateTime>,
+        /// Hold the theme against the schedule until a time (`18:00`) or date and time
+        /// (`2025-06-01 18:00`)
+        #[arg(long, value_name = "TIME", value_parser = parse_until, global = true)]
+        until: Option<NaiveDateTime>,
     },
     #[command(about = "Toggle the theme between Light and Dark")]
     Toggle,
+    #[command(about = "Show the current theme and portal color-scheme preference")]
+    Status,
+    #[command(
+        about = "Run in the background, following the schedule and serving org.kswitch.Switcher on D-Bus"
+    )]
+    Daemon {
+        /// Apply the style matching the freedesktop color-scheme preference whenever it changes
+        #[arg(long)]
+        follow_portal: bool,
+    },
+    #[command(
+        about = "Pause, resume or inspect the schedule",
+        arg_required_else_help = true,
+        after_help = "
+\x1b[1mExample usage:\x1b[0m
+    kswitch schedule pause
+    kswitch schedule pause --until 17:30
+    kswitch schedule resume
+    kswitch schedule show
+    kswitch schedule next
+    kswitch schedule at \"2025-12-24 08:00\""
+    )]
+    Schedule {
+        #[command(subcommand)]
+        command: ScheduleCommand,
+    },
     #[command(about = "Configure for kswitch", arg_required_else_help = true)]
     Config {
         #[command(subcommand)]
@@ -41,3 +77,175 @@ pub enum ConfigCommand {
     List,
     Edit,
 }
+
+#[derive(Subcommand, Debug, Serialize, Deserialize, PartialEq)]
+pub enum ScheduleCommand {
+    /// Keep the current theme, whatever the schedule says
+    Pause {
+        /// Resume by itself at a time (`17:30`) or date and time (`2025-06-01 17:30`)
+        #[arg(long, value_name = "TIME", value_parser = parse_until)]
+        until: Option<NaiveDateTime>,
+    },
+    /// Follow the schedule again
+    Resume,
+    /// List the schedule windows in order
+    Show,
+    /// Show when the schedule switches theme next
+    Next,
+    /// Show the theme the schedule picks at a time (`06:30`, today) or date and time
+    /// (`2025-12-24 08:00`)
+    At {
+        #[arg(value_name = "DATETIME", value_parser = parse_datetime)]
+        datetime: NaiveDateTime,
+    },
+}
+
+/// Parse a duration made of whole days, hours, minutes and seconds, e.g. `2h` or `1h30m`
+fn parse_duration(s: &str) -> Result<TimeDelta, String> {
+    let mut total = TimeDelta::zero();
+    let mut number = String::new();
+    for c in s.trim().chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let value: i64 = number
+            .parse()
+            .map_err(|_| format!("Invalid duration: {}", s))?;
+        let delta = match c {
+            'd' => TimeDelta::try_days(value),
+            'h' => TimeDelta::try_hours(value),
+            'm' => TimeDelta::try_minutes(value),
+            's' => TimeDelta::try_seconds(value),
+            _ => return Err(format!("Invalid duration unit '{}' in {}", c, s)),
+        };
+        total = delta
+            .and_then(|delta| total.checked_add(&delta))
+            .ok_or(format!("Duration too long: {}", s))?;
+        number.clear();
+    }
+    if !number.is_empty() {
+        return Err(format!("Missing unit (d, h, m or s) in duration: {}", s));
+    }
+    match total > TimeDelta::zero() {
+        true => Ok(total),
+        false => Err(format!("Duration must be positive: {}", s)),
+    }
+}
+
+/// `now`, to the second, plus the duration in `s`
+fn for_from(s: &str, now: &NaiveDateTime) -> Result<NaiveDateTime, String> {
+    now.with_nanosecond(0)
+        .unwrap_or(*now)
+        .checked_add_signed(parse_duration(s)?)
+        .ok_or(format!("Duration too long: {}", s))
+}
+
+fn parse_for(s: &str) -> Result<NaiveDateTime, String> {
+    for_from(s, &Local::now().naive_local())
+}
+
+fn time_of_day(s: &str) -> Option<NaiveTime> {
+    ["%H:%M", "%H:%M:%S"]
+        .iter()
+        .find_map(|format| NaiveTime::parse_from_str(s, format).ok())
+}
+
+/// A date and time, or a time of day on the date of `now`
+fn datetime_from(s: &str, now: &NaiveDateTime) -> Result<NaiveDateTime, String> {
+    let s = s.trim();
+    for format in [
+        "%Y-%m-%d %H:%M",
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%dT%H:%M",
+        "%Y-%m-%dT%H:%M:%S",
+    ] {
+        if let Ok(datetime) = NaiveDateTime::parse_from_str(s, format) {
+            return Ok(datetime);
+        }
+    }
+    match time_of_day(s) {
+        Some(time) => Ok(now.date().and_time(time)),
+        None => Err(format!(
+            "Invalid time: {} (expected HH:MM or YYYY-MM-DD HH:MM)",
+            s
+        )),
+    }
+}
+
+/// The first moment at or after `now` described by `s`: a time of day is its next occurrence,
+/// a full date and time is taken as is.
+fn until_from(s: &str, now: &NaiveDateTime) -> Result<NaiveDateTime, String> {
+    let datetime = datetime_from(s, now)?;
+    match time_of_day(s.trim()).is_some() && datetime <= *now {
+        true => datetime
+            .checked_add_signed(TimeDelta::days(1))
+            .ok_or(format!("Time out of range: {}", s)),
+        false => Ok(datetime),
+    }
+}
+
+fn parse_datetime(s: &str) -> Result<NaiveDateTime, String> {
+    datetime_from(s, &Local::now().naive_local())
+}
+
+fn parse_until(s: &str) -> Result<NaiveDateTime, String> {
+    until_from(s, &Local::now().naive_local())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("2h", Some(TimeDelta::hours(2)))]
+    #[case("1h30m", Some(TimeDelta::minutes(90)))]
+    #[case("1d", Some(TimeDelta::days(1)))]
+    #[case("45s", Some(TimeDelta::seconds(45)))]
+    #[case("90", None)]
+    #[case("2x", None)]
+    #[case("h", None)]
+    #[case("0m", None)]
+    #[case("9999999999999999d", None)]
+    #[case("106751991167d106751991167d", None)]
+    fn test_parse_duration(#[case] input: &str, #[case] expected: Option<TimeDelta>) {
+        assert_eq!(parse_duration(input).ok(), expected);
+    }
+
+    #[rstest]
+    #[case("2h", Some("2025-06-01 14:00"))]
+    #[case("99999999d", None)]
+    fn test_for_from(#[case] input: &str, #[case] expected: Option<&str>) {
+        let now = NaiveDateTime::parse_from_str("2025-06-01 12:00", "%Y-%m-%d %H:%M").unwrap();
+        let expected = expected
+            .map(|datetime| NaiveDateTime::parse_from_str(datetime, "%Y-%m-%d %H:%M").unwrap());
+        assert_eq!(for_from(input, &now).ok(), expected);
+    }
+
+    #[rstest]
+    #[case("18:00", Some("2025-06-01 18:00"))]
+    #[case("09:15", Some("2025-06-02 09:15"))]
+    #[case("12:00", Some("2025-06-02 12:00"))]
+    #[case("2025-07-01 08:30", Some("2025-07-01 08:30"))]
+    #[case("2025-07-01T08:30:00", Some("2025-07-01 08:30"))]
+    #[case("tonight", None)]
+    fn test_until_from(#[case] input: &str, #[case] expected: Option<&str>) {
+        let now = NaiveDateTime::parse_from_str("2025-06-01 12:00", "%Y-%m-%d %H:%M").unwrap();
+        let expected = expected
+            .map(|datetime| NaiveDateTime::parse_from_str(datetime, "%Y-%m-%d %H:%M").unwrap());
+        assert_eq!(until_from(input, &now).ok(), expected);
+    }
+
+    #[rstest]
+    #[case("06:30", Some("2025-06-01 06:30"))]
+    #[case("18:00", Some("2025-06-01 18:00"))]
+    #[case("2025-12-24 08:00", Some("2025-12-24 08:00"))]
+    #[case("christmas", None)]
+    fn test_datetime_from(#[case] input: &str, #[case] expected: Option<&str>) {
+        let now = NaiveDateTime::parse_from_str("2025-06-01 12:00", "%Y-%m-%d %H:%M").unwrap();
+        let expected = expected
+            .map(|datetime| NaiveDateTime::parse_from_str(datetime, "%Y-%m-%d %H:%M").unwrap());
+        assert_eq!(datetime_from(input, &now).ok(), expected);
+    }
+}
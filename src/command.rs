@@ -0,0 +1,258 @@
+use std::fmt;
+use std::io::{Error, ErrorKind, Read};
+use std::os::unix::process::CommandExt;
+use std::process::{Child, Command, Output, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How often a running child is checked for exit
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+/// How long to wait for the pipes of a killed child to close
+const DRAIN_AFTER_KILL: Duration = Duration::from_millis(200);
+
+/// Error of a command killed at its timeout, with the output it wrote until then. Returned
+/// inside an error of kind [`ErrorKind::TimedOut`], see [`partial_output`].
+#[derive(Debug)]
+pub struct TimedOut {
+    message: String,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+}
+
+impl fmt::Display for TimedOut {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for TimedOut {}
+
+/// Output a command wrote before it was killed, if `error` is its timeout
+pub fn partial_output(error: &Error) -> Option<&TimedOut> {
+    error.get_ref()?.downcast_ref::<TimedOut>()
+}
+
+/// Output of a child pipe, read on its own thread so a chatty child never blocks on a full
+/// pipe while we wait for it
+struct Drain {
+    buffer: Arc<Mutex<Vec<u8>>>,
+    closed: Receiver<()>,
+}
+
+impl Drain {
+    fn new<R: Read + Send + 'static>(pipe: Option<R>) -> Self {
+        let buffer = Arc::new(Mutex::new(Vec::new()));
+        let (sender, closed) = mpsc::channel();
+        let shared = Arc::clone(&buffer);
+        thread::spawn(move || {
+            if let Some(mut pipe) = pipe {
+                let mut chunk = [0; 4096];
+                while let Ok(read @ 1..) = pipe.read(&mut chunk) {
+                    shared.lock().unwrap().extend_from_slice(&chunk[..read]);
+                }
+            }
+            let _ = sender.send(());
+        });
+        Drain { buffer, closed }
+    }
+
+    /// Everything read once the pipe closed, or what was read by `deadline` if a process the
+    /// child left running in the background still holds the pipe open
+    fn collect(self, deadline: Instant) -> Vec<u8> {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if self.closed.recv_timeout(remaining).is_err() {
+            log::debug!("Output still open after the child exited, not waiting for it");
+        }
+        std::mem::take(&mut *self.buffer.lock().unwrap())
+    }
+}
+
+/// Kill the child and everything it started. The child leads its own process group, so the
+/// group reaches processes it put in the background too.
+fn kill(child: &mut Child) {
+    // SAFETY: killpg only sends a signal; the group id is the pid of our own child
+    if unsafe { libc::killpg(child.id() as libc::pid_t, libc::SIGKILL) } != 0 {
+        log::warn!(
+            "Failed to kill process group {}: {}",
+            child.id(),
+            Error::last_os_error()
+        );
+        let _ = child.kill();
+    }
+    // Reap the child so it does not linger as a zombie
+    let _ = child.wait();
+}
+
+/// `timeout`, shortened so that it runs out by `deadline` at the latest
+pub fn within(timeout: Duration, deadline: Option<Instant>) -> Duration {
+    match deadline {
+        Some(deadline) => timeout.min(deadline.saturating_duration_since(Instant::now())),
+        None => timeout,
+    }
+}
+
+/// Like [`Command::output`], but kills the child and its process group once `timeout`
+/// elapsed and returns an error of kind [`ErrorKind::TimedOut`]. Processes the child leaves
+/// running in the background are not waited for past `timeout`. Nothing is started with a zero
+/// `timeout`.
+pub fn output(command: &mut Command, timeout: Duration) -> Result<Output, Error> {
+    let program = command.get_program().to_string_lossy().to_string();
+    if timeout.is_zero() {
+        return Err(Error::new(
+            ErrorKind::TimedOut,
+            format!("{} not started, no time left", program),
+        ));
+    }
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .process_group(0)
+        .spawn()?;
+
+    let stdout = Drain::new(child.stdout.take());
+    let stderr = Drain::new(child.stderr.take());
+
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if Instant::now() >= deadline => {
+                log::warn!(
+                    "{} did not finish within {} s, killing it",
+                    program,
+                    timeout.as_secs_f32()
+                );
+                kill(&mut child);
+                let drained = Instant::now() + DRAIN_AFTER_KILL;
+                return Err(Error::new(
+                    ErrorKind::TimedOut,
+                    TimedOut {
+                        message: format!("{} timed out after {} s", program, timeout.as_secs_f32()),
+                        stdout: stdout.collect(drained),
+                        stderr: stderr.collect(drained),
+                    },
+                ));
+            }
+            Ok(None) => thread::sleep(POLL_INTERVAL),
+            Err(e) => {
+                kill(&mut child);
+                return Err(e);
+            }
+        }
+    };
+
+    Ok(Output {
+        status,
+        stdout: stdout.collect(deadline),
+        stderr: stderr.collect(deadline),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_output_finishes() {
+        let out = output(
+            Command::new("sh").arg("-c").arg("echo out; echo err >&2"),
+            Duration::from_secs(5),
+        )
+        .unwrap();
+
+        assert!(out.status.success());
+        assert_eq!(out.stdout, b"out\n");
+        assert_eq!(out.stderr, b"err\n");
+    }
+
+    #[test]
+    fn test_output_times_out() {
+        let start = Instant::now();
+        let err = output(Command::new("sleep").arg("10"), Duration::from_millis(200)).unwrap_err();
+
+        assert_eq!(err.kind(), ErrorKind::TimedOut);
+        assert!(start.elapsed() < Duration::from_secs(2));
+    }
+
+    #[test]
+    fn test_output_keeps_partial_output_on_timeout() {
+        let err = output(
+            Command::new("sh")
+                .arg("-c")
+                .arg("echo started; echo waiting >&2; sleep 10"),
+            Duration::from_millis(300),
+        )
+        .unwrap_err();
+
+        assert_eq!(err.kind(), ErrorKind::TimedOut);
+        assert!(err.to_string().starts_with("sh timed out after"));
+        let partial = partial_output(&err).unwrap();
+        assert_eq!(partial.stdout, b"started\n");
+        assert_eq!(partial.stderr, b"waiting\n");
+    }
+
+    #[test]
+    fn test_output_without_time_left() {
+        let err = output(&mut Command::new("true"), Duration::ZERO).unwrap_err();
+
+        assert_eq!(err.kind(), ErrorKind::TimedOut);
+    }
+
+    #[test]
+    fn test_within() {
+        let deadline = Instant::now() + Duration::from_secs(2);
+
+        assert_eq!(
+            within(Duration::from_secs(1), Some(deadline)),
+            Duration::from_secs(1)
+        );
+        assert!(within(Duration::from_secs(5), Some(deadline)) <= Duration::from_secs(2));
+        assert_eq!(within(Duration::from_secs(5), None), Duration::from_secs(5));
+        assert_eq!(
+            within(
+                Duration::from_secs(5),
+                Some(Instant::now() - Duration::from_secs(1))
+            ),
+            Duration::ZERO
+        );
+    }
+
+    #[test]
+    fn test_output_does_not_wait_for_background_processes() {
+        let start = Instant::now();
+        let out = output(
+            Command::new("sh").arg("-c").arg("sleep 5 & echo hi"),
+            Duration::from_secs(1),
+        )
+        .unwrap();
+
+        assert!(out.status.success());
+        assert_eq!(out.stdout, b"hi\n");
+        assert!(start.elapsed() < Duration::from_secs(3));
+    }
+
+    #[test]
+    fn test_output_kills_process_group() {
+        let pid_file = std::env::temp_dir().join("kswitch_test_process_group.pid");
+        let _ = std::fs::remove_file(&pid_file);
+        let script = format!("sleep 30 & echo $! > '{}'; wait", pid_file.display());
+
+        let err = output(
+            Command::new("sh").arg("-c").arg(script),
+            Duration::from_millis(300),
+        )
+        .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::TimedOut);
+
+        // The background sleep is gone, or a zombie waiting for init to reap it
+        thread::sleep(Duration::from_millis(100));
+        let pid = std::fs::read_to_string(&pid_file).unwrap();
+        let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid.trim()));
+        assert!(stat.is_err() || stat.is_ok_and(|stat| stat.contains(") Z ")));
+
+        let _ = std::fs::remove_file(&pid_file);
+    }
+}
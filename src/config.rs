@@ -1,18 +1,23 @@
 use dirs;
 use log;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::error::Error;
 use std::fs::{File, create_dir_all};
 use std::io::{Read, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::{env, fs};
 use toml;
 
+use crate::daemon::DaemonOptions;
+use crate::phases::{Phase, Timeouts, default_phases, is_default_phases};
 use crate::schedule::Schedule;
-use crate::theme::Style;
+use crate::scripts::ScriptOptions;
+use crate::set::gtk::GtkOptions;
+use crate::theme::{Style, Wallpaper};
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Config {
     #[serde(skip)]
     pub path: PathBuf,
@@ -20,10 +25,44 @@ pub struct Config {
     pub light_scripts_dir: PathBuf,
     #[serde(skip)]
     pub dark_scripts_dir: PathBuf,
+    /// Scripts run for both themes
+    #[serde(skip)]
+    pub any_scripts_dir: PathBuf,
     pub light: Style,
     pub dark: Style,
     pub schedule: Schedule,
     pub konsolerc: PathBuf,
+    /// Order in which components are applied, see [`default_phases`]
+    #[serde(default = "default_phases", skip_serializing_if = "is_default_phases")]
+    pub phases: Vec<Phase>,
+    #[serde(default)]
+    pub timeouts: Timeouts,
+    #[serde(default)]
+    pub scripts: ScriptOptions,
+    #[serde(default)]
+    pub daemon: DaemonOptions,
+    #[serde(default)]
+    pub gtk: GtkOptions,
+}
+
+/// Open a config file in `$EDITOR`. Works on files that fail to load, so they can be fixed.
+pub fn edit(path: &Path) -> Result<(), Box<dyn Error>> {
+    let editor = env::var("EDITOR").unwrap_or_else(|_| "nano".to_string());
+
+    // spawn the editor process
+    let mut command = Command::new(editor);
+    command.arg(path);
+
+    // execute command
+    match command.spawn() {
+        Ok(mut child) => match child.wait() {
+            Ok(status) => log::info!("Editor exited with status: {}", status),
+            Err(e) => log::error!("Failed to wait on editor process: {}", e),
+        },
+        Err(e) => log::error!("Failed to start editor: {}", e),
+    };
+
+    Ok(())
 }
 
 impl Default for Config {
@@ -31,24 +70,53 @@ impl Default for Config {
         let path = dirs::config_dir().unwrap().join("kswitch/config.toml");
 
         let light_style = Style {
-            wallpaper: PathBuf::from("/usr/share/wallpapers/Bamboo/contents/images/5120x2880.png"),
+            wallpaper: Wallpaper::Single(PathBuf::from(
+                "/usr/share/wallpapers/Bamboo/contents/images/5120x2880.png",
+            )),
             color_scheme: String::from("BreathLight"),
             desktop_theme: String::from("breath"),
             terminal_profile: String::from("light"),
+            icon_theme: None,
+            cursor_theme: None,
+            cursor_size: None,
+            gtk_theme: None,
+            window_decoration: None,
+            lockscreen_wallpaper: None,
+            neovim: None,
+            vscode_theme: None,
+            tmux_theme: None,
+            bat_theme: None,
+            delta_theme: None,
+            fzf_colors: None,
+            app_color_schemes: BTreeMap::new(),
         };
         let dark_style = Style {
-            wallpaper: PathBuf::from(
+            wallpaper: Wallpaper::Single(PathBuf::from(
                 "/usr/share/wallpapers/Bamboo at Night/contents/images/5120x2880.png",
-            ),
+            )),
             color_scheme: String::from("BreathDark"),
             desktop_theme: String::from("breath-dark"),
             terminal_profile: String::from("dark"),
+            icon_theme: None,
+            cursor_theme: None,
+            cursor_size: None,
+            gtk_theme: None,
+            window_decoration: None,
+            lockscreen_wallpaper: None,
+            neovim: None,
+            vscode_theme: None,
+            tmux_theme: None,
+            bat_theme: None,
+            delta_theme: None,
+            fzf_colors: None,
+            app_color_schemes: BTreeMap::new(),
         };
         let schedule = Schedule::default();
         Config {
             path: path.clone(),
             light_scripts_dir: path.parent().unwrap().join("light"),
             dark_scripts_dir: path.parent().unwrap().join("dark"),
+            any_scripts_dir: path.parent().unwrap().join("any"),
             light: light_style,
             dark: dark_style,
             schedule: schedule,
@@ -57,6 +125,11 @@ impl Default for Config {
                     .unwrap_or(PathBuf::from("~/.config"))
                     .join("konsolerc"),
             ),
+            phases: default_phases(),
+            timeouts: Timeouts::default(),
+            scripts: ScriptOptions::default(),
+            daemon: DaemonOptions::default(),
+            gtk: GtkOptions::default(),
         }
     }
 }
@@ -85,22 +158,7 @@ impl Config {
     }
 
     pub fn edit(&self) -> Result<(), Box<dyn Error>> {
-        let editor = env::var("EDITOR").unwrap_or_else(|_| "nano".to_string());
-
-        // spawn the editor process
-        let mut command = Command::new(editor);
-        command.arg(&self.path);
-
-        // execute command
-        match command.spawn() {
-            Ok(mut child) => match child.wait() {
-                Ok(status) => log::info!("Editor exited with status: {}", status),
-                Err(e) => log::error!("Failed to wait on editor process: {}", e),
-            },
-            Err(e) => log::error!("Failed to start editor: {}", e),
-        };
-
-        Ok(())
+        edit(&self.path)
     }
 
     pub fn load(file_path: &PathBuf) -> Result<Config, Box<dyn Error>> {
@@ -113,18 +171,20 @@ impl Config {
 
         // Deserialize the TOML string into a Config struct
         let mut config: Config = toml::from_str(&contents)?;
+        config.schedule.validate()?;
         config.path = dirs::config_dir().unwrap().join("kswitch/config.toml");
 
         // create script dirs if needed
         config.light_scripts_dir = config.path.parent().unwrap().join("light");
         config.dark_scripts_dir = config.path.parent().unwrap().join("dark");
+        config.any_scripts_dir = config.path.parent().unwrap().join("any");
         config.create_script_dirs();
 
         log::info!("Loaded config from {}", file_path.to_string_lossy());
         Ok(config)
     }
 
-    /// Create light and dark script dirs if they do not exist
+    /// Create light, dark and shared script dirs if they do not exist
     fn create_script_dirs(&self) {
         // Light
         if !&self.light_scripts_dir.is_dir() {
@@ -153,6 +213,20 @@ impl Config {
                 ),
             }
         }
+
+        // Shared
+        if !&self.any_scripts_dir.is_dir() {
+            match fs::create_dir(&self.any_scripts_dir) {
+                Err(_) => log::error!(
+                    "Failed to create scripts dir at: {}",
+                    &self.any_scripts_dir.to_string_lossy()
+                ),
+                Ok(_) => log::info!(
+                    "Created scripts dir at: {}",
+                    &self.any_scripts_dir.to_string_lossy()
+                ),
+            }
+        }
     }
 }
 
@@ -162,27 +236,58 @@ mod tests {
     use std::fs::remove_file;
 
     use super::*;
+    use crate::schedule::{ScheduleError, Window};
+    use crate::theme::Theme;
 
     #[test]
     fn test_create_and_load_config() {
         let light_style = Style {
-            wallpaper: PathBuf::from("/usr/share/wallpapers/Bamboo/contents/images/5120x2880.png"),
+            wallpaper: Wallpaper::Single(PathBuf::from(
+                "/usr/share/wallpapers/Bamboo/contents/images/5120x2880.png",
+            )),
             color_scheme: String::from("BreathLight"),
             desktop_theme: String::from("breath"),
             terminal_profile: String::from("light"),
+            icon_theme: Some(String::from("breeze")),
+            cursor_theme: None,
+            cursor_size: None,
+            gtk_theme: None,
+            window_decoration: None,
+            lockscreen_wallpaper: None,
+            neovim: None,
+            vscode_theme: None,
+            tmux_theme: None,
+            bat_theme: None,
+            delta_theme: None,
+            fzf_colors: None,
+            app_color_schemes: BTreeMap::new(),
         };
         let dark_style = Style {
-            wallpaper: PathBuf::from(
+            wallpaper: Wallpaper::Single(PathBuf::from(
                 "/usr/share/wallpapers/Bamboo at Night/contents/images/5120x2880.png",
-            ),
+            )),
             color_scheme: String::from("BreathDark"),
             desktop_theme: String::from("breath-dark"),
             terminal_profile: String::from("light"),
+            icon_theme: None,
+            cursor_theme: None,
+            cursor_size: None,
+            gtk_theme: None,
+            window_decoration: None,
+            lockscreen_wallpaper: None,
+            neovim: None,
+            vscode_theme: None,
+            tmux_theme: None,
+            bat_theme: None,
+            delta_theme: None,
+            fzf_colors: None,
+            app_color_schemes: BTreeMap::new(),
         };
         let conf = Config {
             path: temp_dir().join("test_config.toml"),
             light_scripts_dir: temp_dir().join("test_config_light"),
             dark_scripts_dir: temp_dir().join("test_config_dark"),
+            any_scripts_dir: temp_dir().join("test_config_any"),
             light: light_style,
             dark: dark_style,
             schedule: Schedule::default(),
@@ -191,6 +296,11 @@ mod tests {
                     .unwrap_or(PathBuf::from("~/.config"))
                     .join("konsolerc"),
             ),
+            phases: default_phases(),
+            timeouts: Timeouts::default(),
+            scripts: ScriptOptions::default(),
+            daemon: DaemonOptions::default(),
+            gtk: GtkOptions::default(),
         };
 
         let _ = conf.save();
@@ -202,4 +312,83 @@ mod tests {
 
         let _ = remove_file(loaded.path);
     }
+
+    #[test]
+    fn test_load_rejects_empty_schedule() {
+        let path = temp_dir().join("kswitch_test_empty_schedule.toml");
+        let mut config = Config::default();
+        config.schedule.windows.clear();
+        fs::write(&path, toml::to_string(&config).unwrap()).unwrap();
+
+        let error = Config::load(&path).unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<ScheduleError>(),
+            Some(&ScheduleError::Empty)
+        );
+
+        let _ = remove_file(&path);
+    }
+
+    #[test]
+    fn test_load_rejects_conflicting_schedule() {
+        let path = temp_dir().join("kswitch_test_conflicting_schedule.toml");
+        let mut config = Config::default();
+        let start = config.schedule.windows[0].start;
+        config
+            .schedule
+            .windows
+            .push(Window::daily(Theme::Dark, start));
+        fs::write(&path, toml::to_string(&config).unwrap()).unwrap();
+
+        let error = Config::load(&path).unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<ScheduleError>(),
+            Some(&ScheduleError::Conflict {
+                start,
+                first: 1,
+                second: 3,
+            })
+        );
+
+        let _ = remove_file(&path);
+    }
+
+    #[test]
+    fn test_default_phases_are_not_saved() {
+        let mut config = Config::default();
+        assert!(!toml::to_string(&config).unwrap().contains("[[phases]]"));
+
+        config.phases.pop();
+        let saved = toml::to_string(&config).unwrap();
+        assert!(saved.contains("[[phases]]"));
+        let loaded: Config = toml::from_str(&saved).unwrap();
+        assert_eq!(loaded.phases, config.phases);
+    }
+
+    #[test]
+    fn test_load_rejects_mistyped_wallpaper_directory() {
+        let path = temp_dir().join("kswitch_test_wallpaper_directory.toml");
+        let mut config = Config::default();
+        config.dark.wallpaper = Wallpaper::PerDesktop(BTreeMap::from([
+            (String::from("directory"), PathBuf::from("/w/night")),
+            (String::from("mode"), PathBuf::from("roundrobin")),
+        ]));
+        fs::write(&path, toml::to_string(&config).unwrap()).unwrap();
+
+        let error = Config::load(&path).unwrap_err().to_string();
+        assert!(error.contains("invalid wallpaper directory"), "{}", error);
+        assert!(error.contains("unknown variant `roundrobin`"), "{}", error);
+
+        config.dark.wallpaper = Wallpaper::PerDesktop(BTreeMap::from([
+            (String::from("DP-1"), PathBuf::from("/w/night/left.png")),
+            (String::from("*"), PathBuf::from("/w/night")),
+        ]));
+        fs::write(&path, toml::to_string(&config).unwrap()).unwrap();
+        assert_eq!(
+            Config::load(&path).unwrap().dark.wallpaper,
+            config.dark.wallpaper
+        );
+
+        let _ = remove_file(&path);
+    }
 }
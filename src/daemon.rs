@@ -0,0 +1,440 @@
+use std::error::Error;
+use std::str::FromStr;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::time::{Duration, Instant};
+
+use chrono::{Local, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+use zbus::blocking::Connection;
+use zbus::blocking::connection::Builder;
+use zbus::names::BusName;
+use zbus::object_server::SignalEmitter;
+use zbus::{fdo, interface};
+
+use crate::config::Config;
+use crate::get::external_theme;
+use crate::operations::{set, toggle};
+use crate::phases::{self, Component};
+use crate::report::{SwitchReport, Trigger};
+use crate::state::{StateManager, SwitchLock};
+use crate::theme::Theme;
+use crate::watch;
+
+pub const BUS_NAME: &str = "org.kswitch.Switcher";
+pub const OBJECT_PATH: &str = "/org/kswitch/Switcher";
+pub const INTERFACE: &str = "org.kswitch.Switcher";
+
+/// How often the schedule is checked
+const SCHEDULE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Quiet time after the last external change before it is evaluated, so the burst of writes
+/// of one switch in System Settings is evaluated once. Changes made while a `kswitch` switch
+/// holds the [`SwitchLock`] are ignored, however long it takes.
+const EXTERNAL_DEBOUNCE: Duration = Duration::from_secs(3);
+
+/// Components an external color switch has already taken care of
+const EXTERNAL_COMPONENTS: [Component; 2] = [Component::ColorScheme, Component::DesktopTheme];
+
+/// Components a portal color-scheme change has already taken care of
+const PORTAL_COMPONENTS: [Component; 3] = [
+    Component::ColorScheme,
+    Component::DesktopTheme,
+    Component::Portal,
+];
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DaemonOptions {
+    /// Notice when the color scheme or desktop theme is changed outside kswitch
+    #[serde(default = "default_true")]
+    pub watch_external_changes: bool,
+    /// After an external change, also apply the rest of the matching style
+    #[serde(default)]
+    pub reapply_on_external_change: bool,
+    /// Apply the style matching the settings portal color-scheme whenever it changes
+    #[serde(default)]
+    pub follow_portal: bool,
+}
+
+impl Default for DaemonOptions {
+    fn default() -> Self {
+        DaemonOptions {
+            watch_external_changes: true,
+            reapply_on_external_change: false,
+            follow_portal: false,
+        }
+    }
+}
+
+/// Work handed from the D-Bus interface to the daemon loop
+#[derive(Debug, PartialEq)]
+pub enum Request {
+    Set(Theme, Trigger),
+    Toggle(Trigger),
+    /// kdeglobals, plasmarc or the KDE palette changed, possibly by another program
+    External,
+    /// The settings portal color-scheme now prefers this theme
+    Portal(Theme),
+}
+
+/// The `org.kswitch.Switcher` interface. Switches are queued for the daemon loop, so callers
+/// return right away and learn about the result from `ThemeChanged`.
+pub struct Switcher {
+    requests: Sender<Request>,
+}
+
+impl Switcher {
+    fn queue(&self, request: Request) -> fdo::Result<()> {
+        log::debug!("Queueing {:?}", request);
+        self.requests
+            .send(request)
+            .map_err(|e| fdo::Error::Failed(format!("Daemon is shutting down: {}", e)))
+    }
+}
+
+#[interface(name = "org.kswitch.Switcher")]
+impl Switcher {
+    /// Switch to `light` or `dark`.
+    fn set(&self, theme: &str) -> fdo::Result<()> {
+        let theme = Theme::from_str(theme)
+            .map_err(|_| fdo::Error::InvalidArgs(format!("Unknown theme: {}", theme)))?;
+        self.queue(Request::Set(theme, Trigger::DBus))
+    }
+
+    /// Switch to the other theme.
+    fn toggle(&self) -> fdo::Result<()> {
+        self.queue(Request::Toggle(Trigger::DBus))
+    }
+
+    /// The theme kswitch last applied.
+    fn current(&self) -> fdo::Result<String> {
+        StateManager::new()
+            .and_then(|state_manager| state_manager.load())
+            .map(|state| state.current_theme.to_string())
+            .map_err(|e| fdo::Error::Failed(e.to_string()))
+    }
+
+    /// Emitted after every switch, with the trigger that caused it (`set`, `toggle`,
+    /// `schedule`, `dbus`, ...).
+    #[zbus(signal)]
+    async fn theme_changed(
+        emitter: &SignalEmitter<'_>,
+        theme: &str,
+        trigger: &str,
+    ) -> zbus::Result<()>;
+}
+
+struct Daemon {
+    config: Config,
+    connection: Connection,
+    /// Theme of the schedule window at the last check
+    scheduled: Option<Theme>,
+    /// Time of the last external or portal change not evaluated yet
+    changed: Option<Instant>,
+    /// Whether kdeglobals or plasmarc changed since the last evaluation
+    external: bool,
+    /// Latest portal preference not evaluated yet
+    portal: Option<Theme>,
+    /// Whether the schedule was paused at the last check
+    paused: bool,
+}
+
+impl Daemon {
+    fn theme_changed(&self, theme: &Theme, trigger: Trigger) {
+        let body = (theme.to_string(), trigger.to_string());
+        if let Err(e) = self.connection.emit_signal(
+            None::<BusName>,
+            OBJECT_PATH,
+            INTERFACE,
+            "ThemeChanged",
+            &body,
+        ) {
+            log::warn!("Failed to emit ThemeChanged: {}", e);
+        }
+    }
+
+    fn switch(&self, theme: &Theme, config: &Config, trigger: Trigger) -> SwitchReport {
+        let report = set(theme, config, trigger);
+        self.announce(&report);
+        report
+    }
+
+    /// Log the problems of a switch and tell D-Bus listeners about it
+    fn announce(&self, report: &SwitchReport) {
+        for problem in report.problems() {
+            log::warn!("{} {}", problem.component, problem.outcome);
+        }
+        if !report.aborted {
+            self.theme_changed(&report.theme, report.trigger);
+        }
+    }
+
+    fn current_theme(&self) -> Option<Theme> {
+        StateManager::new()
+            .and_then(|state_manager| state_manager.load())
+            .map(|state| state.current_theme)
+            .ok()
+    }
+
+    /// Apply the style the portal now prefers, leaving out what the change already covered.
+    fn check_portal(&self, theme: &Theme) {
+        if self.current_theme().as_ref() == Some(theme) {
+            return;
+        }
+
+        log::info!("Portal color-scheme switched to {}", theme.to_string());
+        let mut config = self.config.clone();
+        config.phases = phases::without(&config.phases, &PORTAL_COMPONENTS);
+        self.switch(theme, &config, Trigger::Portal);
+    }
+
+    /// Bring the state in line with a theme switched outside kswitch.
+    fn check_external(&self) {
+        let Some(theme) = external_theme::get(&self.config) else {
+            log::debug!("Active color scheme matches no style");
+            return;
+        };
+        if self.current_theme().as_ref() == Some(&theme) {
+            return;
+        }
+
+        log::info!("Theme switched to {} outside kswitch", theme.to_string());
+        match self.config.daemon.reapply_on_external_change {
+            true => {
+                let mut config = self.config.clone();
+                config.phases = phases::without(&config.phases, &EXTERNAL_COMPONENTS);
+                self.switch(&theme, &config, Trigger::External);
+            }
+            false => {
+                match StateManager::new().and_then(|state_manager| state_manager.save(&theme)) {
+                    Ok(_) => self.theme_changed(&theme, Trigger::External),
+                    Err(e) => log::warn!("Failed to save theme state: {}", e),
+                }
+            }
+        }
+    }
+
+    fn handle(&mut self, request: Request) {
+        log::debug!("Handling {:?}", request);
+        match request {
+            Request::Set(theme, trigger) => {
+                self.switch(&theme, &self.config, trigger);
+            }
+            Request::Toggle(trigger) => {
+                // Same as `kswitch toggle`, which also ends a manual override
+                let report = toggle(&self.config, trigger);
+                self.announce(&report);
+            }
+            Request::External | Request::Portal(_) if SwitchLock::held() => {
+                log::debug!("Ignoring change made during a switch");
+            }
+            Request::External => {
+                self.changed = Some(Instant::now());
+                self.external = true;
+            }
+            Request::Portal(theme) => {
+                self.changed = Some(Instant::now());
+                self.portal = Some(theme);
+            }
+        }
+    }
+
+    /// Hold off the schedule while it is paused or a manual override lasts, and clear either
+    /// once it ended. Returns whether the schedule may switch.
+    fn check_hold(&mut self, now: &NaiveDateTime) -> bool {
+        let Ok(state_manager) = StateManager::new() else {
+            return true;
+        };
+        let Ok(state) = state_manager.load() else {
+            return true;
+        };
+
+        if state.is_paused(now) {
+            if !self.paused {
+                log::info!("Schedule paused");
+                self.paused = true;
+            }
+            return false;
+        }
+        if self.paused || state.schedule_paused {
+            log::info!("Schedule resumed");
+            self.paused = false;
+            if state.schedule_paused
+                && let Err(e) = state_manager.update(|state| {
+                    state.schedule_paused = false;
+                    state.paused_until = None;
+                })
+            {
+                log::warn!("Failed to clear expired pause: {}", e);
+            }
+            // Apply the current window even if it did not change during the pause
+            self.scheduled = None;
+        }
+
+        let Some(manual_override) = state.manual_override else {
+            return true;
+        };
+        if manual_override.is_active(now) {
+            log::debug!(
+                "Schedule held by override until {}",
+                manual_override.until.format("%Y-%m-%d %H:%M")
+            );
+            return false;
+        }
+
+        log::info!(
+            "Override of {} expired, resuming the schedule",
+            manual_override.theme.to_string()
+        );
+        if let Err(e) = state_manager.update(|state| state.manual_override = None) {
+            log::warn!("Failed to clear expired override: {}", e);
+        }
+        // Evaluate the window again even if it did not change while the override held
+        self.scheduled = None;
+        true
+    }
+
+    /// Switch when a new schedule window started. At startup, and when a pause or override
+    /// ended, switch if the current theme does not match the window.
+    fn check_schedule(&mut self) {
+        let now = Local::now().naive_local();
+        if !self.check_hold(&now) {
+            return;
+        }
+        let theme = match self.config.schedule.theme_from_time(&now) {
+            Ok(theme) => theme,
+            Err(e) => {
+                log::warn!("Cannot follow the schedule: {}", e);
+                return;
+            }
+        };
+        if self.scheduled.as_ref() == Some(&theme) {
+            return;
+        }
+
+        if self.current_theme().as_ref() != Some(&theme) {
+            log::info!("Schedule window changed to {}", theme.to_string());
+            self.switch(&theme, &self.config, Trigger::Schedule);
+        }
+        self.scheduled = Some(theme);
+    }
+
+    /// Evaluate the changes seen since the last quiet period. The portal goes first, so an
+    /// external change it caused is already in the state when that is checked.
+    fn check_changes(&mut self) {
+        self.changed = None;
+        if SwitchLock::held() {
+            log::debug!("A switch started since the last change, ignoring the changes");
+            self.portal = None;
+            self.external = false;
+            return;
+        }
+        if let Some(theme) = self.portal.take() {
+            self.check_portal(&theme);
+        }
+        if self.external {
+            self.external = false;
+            self.check_external();
+        }
+    }
+
+    fn run(&mut self, requests: Receiver<Request>) {
+        loop {
+            self.check_schedule();
+            if let Some(changed) = self.changed
+                && changed.elapsed() >= EXTERNAL_DEBOUNCE
+            {
+                self.check_changes();
+            }
+
+            let timeout = match self.changed {
+                Some(changed) => EXTERNAL_DEBOUNCE.saturating_sub(changed.elapsed()),
+                None => SCHEDULE_INTERVAL,
+            };
+            match requests.recv_timeout(timeout) {
+                Ok(request) => self.handle(request),
+                Err(RecvTimeoutError::Timeout) => continue,
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+        }
+    }
+}
+
+/// Own `org.kswitch.Switcher` on the session bus and follow the schedule until killed.
+pub fn run(config: &Config) -> Result<(), Box<dyn Error>> {
+    let (sender, receiver) = mpsc::channel();
+    if config.daemon.watch_external_changes {
+        watch::spawn(&sender);
+    }
+    if config.daemon.follow_portal {
+        watch::spawn_portal(&sender);
+    }
+    let connection = Builder::session()?
+        .serve_at(OBJECT_PATH, Switcher { requests: sender })?
+        .name(BUS_NAME)?
+        .build()?;
+    log::info!("Serving {} at {}", BUS_NAME, OBJECT_PATH);
+
+    let mut daemon = Daemon {
+        config: config.clone(),
+        connection,
+        scheduled: None,
+        changed: None,
+        external: false,
+        portal: None,
+        paused: false,
+    };
+    daemon.run(receiver);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_io::Async;
+    use std::os::unix::net::UnixStream;
+    use std::thread;
+    use zbus::Guid;
+    use zbus::blocking::Proxy;
+
+    /// Serve a switcher over a peer-to-peer socket and return a client for it.
+    fn switcher() -> (Connection, Connection, Receiver<Request>) {
+        let (sender, receiver) = mpsc::channel();
+        let (server_stream, client_stream) = UnixStream::pair().unwrap();
+        let server = thread::spawn(move || {
+            Builder::socket(Async::new(server_stream).unwrap())
+                .server(Guid::generate())
+                .unwrap()
+                .p2p()
+                .serve_at(OBJECT_PATH, Switcher { requests: sender })
+                .unwrap()
+                .build()
+                .unwrap()
+        });
+        let client = Builder::socket(Async::new(client_stream).unwrap())
+            .p2p()
+            .build()
+            .unwrap();
+        (server.join().unwrap(), client, receiver)
+    }
+
+    #[test]
+    fn test_switcher_queues_requests() {
+        let (_server, client, receiver) = switcher();
+        let proxy = Proxy::new(&client, BUS_NAME, OBJECT_PATH, INTERFACE).unwrap();
+
+        proxy.call_method("Set", &("dark")).unwrap();
+        proxy.call_method("Toggle", &()).unwrap();
+        assert!(proxy.call_method("Set", &("purple")).is_err());
+
+        assert_eq!(
+            receiver.recv().unwrap(),
+            Request::Set(Theme::Dark, Trigger::DBus)
+        );
+        assert_eq!(receiver.recv().unwrap(), Request::Toggle(Trigger::DBus));
+        assert!(receiver.try_recv().is_err());
+    }
+}
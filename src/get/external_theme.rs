@@ -0,0 +1,85 @@
+use std::path::PathBuf;
+
+use crate::config::Config;
+use crate::kconfig;
+use crate::theme::Theme;
+
+pub fn kdeglobals_path() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or(PathBuf::from("~/.config"))
+        .join("kdeglobals")
+}
+
+pub fn plasmarc_path() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or(PathBuf::from("~/.config"))
+        .join("plasmarc")
+}
+
+/// The style whose color scheme, or failing that desktop theme, is the active one.
+fn match_style(
+    config: &Config,
+    color_scheme: Option<&str>,
+    desktop_theme: Option<&str>,
+) -> Option<Theme> {
+    let by_color_scheme = color_scheme.and_then(|name| {
+        match (
+            config.light.color_scheme == name,
+            config.dark.color_scheme == name,
+        ) {
+            (true, false) => Some(Theme::Light),
+            (false, true) => Some(Theme::Dark),
+            _ => None,
+        }
+    });
+    let by_desktop_theme = desktop_theme.and_then(|name| {
+        match (
+            config.light.desktop_theme == name,
+            config.dark.desktop_theme == name,
+        ) {
+            (true, false) => Some(Theme::Light),
+            (false, true) => Some(Theme::Dark),
+            _ => None,
+        }
+    });
+    by_color_scheme.or(by_desktop_theme)
+}
+
+/// The theme matching what Plasma currently uses, as read from `kdeglobals` and `plasmarc`.
+///
+/// Returns `None` when the active color scheme and desktop theme belong to neither style, e.g.
+/// after the user picked something unrelated in System Settings.
+pub fn get(config: &Config) -> Option<Theme> {
+    let color_scheme = kconfig::read_entry(&kdeglobals_path(), &["General"], "ColorScheme");
+    let desktop_theme = kconfig::read_entry(&plasmarc_path(), &["Theme"], "name");
+    log::debug!(
+        "Active color scheme {:?}, desktop theme {:?}",
+        color_scheme,
+        desktop_theme
+    );
+    match_style(config, color_scheme.as_deref(), desktop_theme.as_deref())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(Some("BreathDark"), Some("breath"), Some(Theme::Dark))]
+    #[case(Some("BreathLight"), None, Some(Theme::Light))]
+    #[case(Some("Custom"), Some("breath-dark"), Some(Theme::Dark))]
+    #[case(None, Some("breath"), Some(Theme::Light))]
+    #[case(Some("Custom"), Some("custom"), None)]
+    #[case(None, None, None)]
+    fn test_match_style(
+        #[case] color_scheme: Option<&str>,
+        #[case] desktop_theme: Option<&str>,
+        #[case] expected: Option<Theme>,
+    ) {
+        assert_eq!(
+            match_style(&Config::default(), color_scheme, desktop_theme),
+            expected
+        );
+    }
+}
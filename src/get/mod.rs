@@ -1 +1,3 @@
+pub mod external_theme;
+pub mod portal;
 pub mod target_theme;
@@ -0,0 +1,303 @@
+use std::fmt;
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use zbus::MatchRule;
+use zbus::blocking::{Connection, MessageIterator, Proxy};
+use zbus::message::Type;
+use zbus::zvariant::{OwnedValue, Value};
+
+use crate::theme::Theme;
+
+const APPEARANCE_NAMESPACE: &str = "org.freedesktop.appearance";
+const COLOR_SCHEME_KEY: &str = "color-scheme";
+
+/// Value of `org.freedesktop.appearance color-scheme` as published by the settings portal.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ColorSchemePreference {
+    NoPreference,
+    PreferDark,
+    PreferLight,
+}
+
+impl ColorSchemePreference {
+    /// The preference the portal should publish for a kswitch theme.
+    pub fn for_theme(theme: &Theme) -> Self {
+        match theme {
+            Theme::Light => ColorSchemePreference::PreferLight,
+            Theme::Dark => ColorSchemePreference::PreferDark,
+        }
+    }
+
+    /// The kswitch theme for the preference, if there is one.
+    pub fn theme(&self) -> Option<Theme> {
+        match self {
+            ColorSchemePreference::PreferLight => Some(Theme::Light),
+            ColorSchemePreference::PreferDark => Some(Theme::Dark),
+            ColorSchemePreference::NoPreference => None,
+        }
+    }
+
+    fn from_u32(value: u32) -> Self {
+        match value {
+            1 => ColorSchemePreference::PreferDark,
+            2 => ColorSchemePreference::PreferLight,
+            _ => ColorSchemePreference::NoPreference,
+        }
+    }
+}
+
+impl fmt::Display for ColorSchemePreference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ColorSchemePreference::NoPreference => write!(f, "no-preference"),
+            ColorSchemePreference::PreferDark => write!(f, "prefer-dark"),
+            ColorSchemePreference::PreferLight => write!(f, "prefer-light"),
+        }
+    }
+}
+
+/// Reads the color-scheme preference through `org.freedesktop.portal.Settings.Read`.
+pub fn read(connection: &Connection) -> zbus::Result<ColorSchemePreference> {
+    log::debug!("Reading color-scheme from the settings portal");
+    let proxy = Proxy::new(
+        connection,
+        "org.freedesktop.portal.Desktop",
+        "/org/freedesktop/portal/desktop",
+        "org.freedesktop.portal.Settings",
+    )?;
+
+    let reply = proxy.call_method("Read", &(APPEARANCE_NAMESPACE, COLOR_SCHEME_KEY))?;
+    let value: OwnedValue = reply.body().deserialize()?;
+
+    // Read wraps the setting in an extra variant, unlike ReadOne
+    let value = match &*value {
+        Value::Value(inner) => u32::try_from(&**inner)?,
+        other => u32::try_from(other)?,
+    };
+
+    let preference = ColorSchemePreference::from_u32(value);
+    log::debug!("Portal color-scheme: {}", preference);
+    Ok(preference)
+}
+
+/// The new color-scheme preference carried by a `SettingChanged(namespace, key, value)` signal,
+/// or `None` if the signal is about another setting.
+pub fn from_setting_changed(
+    namespace: &str,
+    key: &str,
+    value: &Value,
+) -> Option<ColorSchemePreference> {
+    if namespace != APPEARANCE_NAMESPACE || key != COLOR_SCHEME_KEY {
+        return None;
+    }
+    let value = match value {
+        Value::Value(inner) => u32::try_from(&**inner).ok()?,
+        other => u32::try_from(other).ok()?,
+    };
+    Some(ColorSchemePreference::from_u32(value))
+}
+
+/// Match rule for the settings portal's `SettingChanged` signal
+pub fn setting_changed_rule() -> zbus::Result<MatchRule<'static>> {
+    Ok(MatchRule::builder()
+        .msg_type(Type::Signal)
+        .path("/org/freedesktop/portal/desktop")?
+        .interface("org.freedesktop.portal.Settings")?
+        .member("SettingChanged")?
+        .build())
+}
+
+/// Waits until the portal publishes the preference matching `theme` or `timeout` elapses.
+///
+/// The portal picks up configuration changes asynchronously, so a single read straight after
+/// switching is not reliable. If the first read does not match yet, this waits for the
+/// portal's `SettingChanged` signal instead. Returns the last preference seen.
+pub fn wait_for(
+    connection: &Connection,
+    theme: &Theme,
+    timeout: Duration,
+) -> zbus::Result<ColorSchemePreference> {
+    let expected = ColorSchemePreference::for_theme(theme);
+    let deadline = Instant::now() + timeout;
+
+    // Subscribe before reading, so a change in between is not missed
+    let messages = MessageIterator::for_match_rule(setting_changed_rule()?, connection, None)?;
+    let mut preference = read(connection)?;
+    if preference == expected {
+        return Ok(preference);
+    }
+
+    // The iterator blocks without a time limit, so it runs on its own thread, which ends at
+    // the next signal after we stopped listening
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        for message in messages {
+            let Ok(message) = message else {
+                break;
+            };
+            let Ok((namespace, key, value)) =
+                message.body().deserialize::<(String, String, OwnedValue)>()
+            else {
+                continue;
+            };
+            if let Some(preference) = from_setting_changed(&namespace, &key, &value)
+                && sender.send(preference).is_err()
+            {
+                break;
+            }
+        }
+    });
+
+    while let Ok(changed) =
+        receiver.recv_timeout(deadline.saturating_duration_since(Instant::now()))
+    {
+        preference = changed;
+        if preference == expected {
+            break;
+        }
+    }
+    Ok(preference)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_io::Async;
+    use rstest::rstest;
+    use std::os::unix::net::UnixStream;
+    use zbus::blocking::connection::Builder;
+    use zbus::{Guid, fdo, interface};
+
+    /// Stand-in for xdg-desktop-portal's Settings interface
+    struct MockSettings {
+        color_scheme: u32,
+    }
+
+    #[interface(name = "org.freedesktop.portal.Settings")]
+    impl MockSettings {
+        fn read(&self, namespace: &str, key: &str) -> fdo::Result<OwnedValue> {
+            if namespace != APPEARANCE_NAMESPACE || key != COLOR_SCHEME_KEY {
+                return Err(fdo::Error::Failed(String::from(
+                    "Requested setting not found",
+                )));
+            }
+            Ok(Value::Value(Box::new(Value::U32(self.color_scheme)))
+                .try_into()
+                .unwrap())
+        }
+    }
+
+    /// Connect to a mock portal over a peer-to-peer socket.
+    fn mock_portal(color_scheme: u32) -> (Connection, Connection) {
+        let (server_stream, client_stream) = UnixStream::pair().unwrap();
+        let server = thread::spawn(move || {
+            Builder::socket(Async::new(server_stream).unwrap())
+                .server(Guid::generate())
+                .unwrap()
+                .p2p()
+                .serve_at(
+                    "/org/freedesktop/portal/desktop",
+                    MockSettings { color_scheme },
+                )
+                .unwrap()
+                .build()
+                .unwrap()
+        });
+        let client = Builder::socket(Async::new(client_stream).unwrap())
+            .p2p()
+            .build()
+            .unwrap();
+        (server.join().unwrap(), client)
+    }
+
+    #[rstest]
+    #[case(0, ColorSchemePreference::NoPreference)]
+    #[case(1, ColorSchemePreference::PreferDark)]
+    #[case(2, ColorSchemePreference::PreferLight)]
+    fn test_read(#[case] value: u32, #[case] expected: ColorSchemePreference) {
+        let (_server, client) = mock_portal(value);
+        assert_eq!(read(&client).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_wait_for() {
+        let (_server, client) = mock_portal(1);
+        assert_eq!(
+            wait_for(&client, &Theme::Dark, Duration::from_secs(1)).unwrap(),
+            ColorSchemePreference::PreferDark
+        );
+
+        // a mismatch is reported once the timeout elapses
+        assert_eq!(
+            wait_for(&client, &Theme::Light, Duration::from_millis(200)).unwrap(),
+            ColorSchemePreference::PreferDark
+        );
+    }
+
+    #[test]
+    fn test_wait_for_setting_changed() {
+        let (server, client) = mock_portal(1);
+        let emitter = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(200));
+            server
+                .emit_signal(
+                    None::<zbus::names::BusName>,
+                    "/org/freedesktop/portal/desktop",
+                    "org.freedesktop.portal.Settings",
+                    "SettingChanged",
+                    &(APPEARANCE_NAMESPACE, COLOR_SCHEME_KEY, Value::U32(2)),
+                )
+                .unwrap();
+            server
+        });
+
+        let start = Instant::now();
+        assert_eq!(
+            wait_for(&client, &Theme::Light, Duration::from_secs(5)).unwrap(),
+            ColorSchemePreference::PreferLight
+        );
+        assert!(start.elapsed() < Duration::from_secs(2));
+        let _server = emitter.join().unwrap();
+    }
+
+    #[rstest]
+    #[case(
+        "org.freedesktop.appearance",
+        "color-scheme",
+        1,
+        Some(ColorSchemePreference::PreferDark)
+    )]
+    #[case(
+        "org.freedesktop.appearance",
+        "color-scheme",
+        2,
+        Some(ColorSchemePreference::PreferLight)
+    )]
+    #[case("org.freedesktop.appearance", "contrast", 1, None)]
+    #[case("org.gnome.desktop.interface", "color-scheme", 1, None)]
+    fn test_from_setting_changed(
+        #[case] namespace: &str,
+        #[case] key: &str,
+        #[case] value: u32,
+        #[case] expected: Option<ColorSchemePreference>,
+    ) {
+        assert_eq!(
+            from_setting_changed(namespace, key, &Value::U32(value)),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_for_theme() {
+        assert_eq!(
+            ColorSchemePreference::for_theme(&Theme::Dark),
+            ColorSchemePreference::PreferDark
+        );
+        assert_eq!(
+            ColorSchemePreference::for_theme(&Theme::Light),
+            ColorSchemePreference::PreferLight
+        );
+    }
+}
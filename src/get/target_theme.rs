@@ -5,12 +5,15 @@ use chrono::Local;
 use log;
 
 fn get_theme_from_schedule(config: &Config) -> Theme {
-    let time = Local::now().naive_local().time();
+    let time = Local::now().naive_local();
     log::debug!(
         "Determining theme from schedule at time: {}",
-        time.format("%H:%M:%S")
+        time.format("%Y-%m-%d %H:%M:%S")
     );
-    let theme = config.schedule.theme_from_time(&time);
+    let theme = config.schedule.theme_from_time(&time).unwrap_or_else(|e| {
+        log::error!("{}, falling back to light", e);
+        Theme::Light
+    });
     log::debug!("Schedule-based theme: {}", theme.to_string());
     theme
 }
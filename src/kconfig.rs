@@ -0,0 +1,243 @@
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Tells apart the temporary files of concurrent writes from this process
+static WRITES: AtomicUsize = AtomicUsize::new(0);
+
+/// Builds the header line for a (possibly nested) KConfig group, e.g. `[Icons]` or
+/// `[Greeter][Wallpaper][org.kde.image][General]`.
+fn group_header(groups: &[&str]) -> String {
+    groups.iter().map(|g| format!("[{}]", g)).collect()
+}
+
+/// Returns true if `line` holds an entry for `key`, ignoring KConfig flags such as `key[$e]`.
+fn is_entry_for(line: &str, key: &str) -> bool {
+    match line.split_once('=') {
+        Some((name, _)) => {
+            let name = name.trim();
+            name == key
+                || name
+                    .strip_prefix(key)
+                    .is_some_and(|rest| rest.starts_with('['))
+        }
+        None => false,
+    }
+}
+
+/// Reads the value of `key` in the given group of a KConfig file, if present.
+pub fn read_entry(path: &Path, groups: &[&str], key: &str) -> Option<String> {
+    let contents = fs::read_to_string(path).ok()?;
+    let header = group_header(groups);
+
+    let mut in_group = false;
+    for line in contents.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with('[') {
+            in_group = trimmed == header;
+        } else if in_group && is_entry_for(trimmed, key) {
+            return trimmed.split_once('=').map(|(_, v)| v.trim().to_string());
+        }
+    }
+    None
+}
+
+/// Writes `key=value` into the given group of a KConfig file (kdeglobals, kwinrc, ...).
+///
+/// The file, group and key are created as needed; all other lines are kept untouched.
+pub fn write_entry(
+    path: &Path,
+    groups: &[&str],
+    key: &str,
+    value: &str,
+) -> Result<(), Box<dyn Error>> {
+    let header = group_header(groups);
+    log::debug!(
+        "Writing {}{} {}={}",
+        path.to_string_lossy(),
+        header,
+        key,
+        value
+    );
+
+    let contents = match path.is_file() {
+        true => fs::read_to_string(path)?,
+        false => String::new(),
+    };
+    let entry = format!("{}={}", key, value);
+
+    let mut output: Vec<String> = Vec::new();
+    let mut in_group = false;
+    let mut written = false;
+
+    for line in contents.lines() {
+        let trimmed = line.trim();
+
+        if trimmed.starts_with('[') {
+            // leaving our group without having seen the key: append it at the end of the group
+            if in_group && !written {
+                insert_after_last_entry(&mut output, entry.clone());
+                written = true;
+            }
+            in_group = trimmed == header;
+        } else if in_group && !written && is_entry_for(trimmed, key) {
+            output.push(entry.clone());
+            written = true;
+            continue;
+        }
+
+        output.push(line.to_string());
+    }
+
+    if !written {
+        if in_group {
+            insert_after_last_entry(&mut output, entry);
+        } else {
+            if output.last().is_some_and(|l| !l.trim().is_empty()) {
+                output.push(String::new());
+            }
+            output.push(header);
+            output.push(entry);
+        }
+    }
+
+    write_atomically(path, &(output.join("\n") + "\n"))?;
+
+    Ok(())
+}
+
+/// Write a file through a temporary file in the same directory renamed over it, as KConfig
+/// does, so readers never see it empty or half written. A symlink is followed, so the file it
+/// points to is replaced and the link kept.
+fn write_atomically(path: &Path, contents: &str) -> io::Result<()> {
+    let path = fs::canonicalize(path).unwrap_or(path.to_path_buf());
+    let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+        return fs::write(&path, contents);
+    };
+    fs::create_dir_all(parent)?;
+
+    let temporary = parent.join(format!(
+        ".{}.kswitch-{}-{}",
+        name.to_string_lossy(),
+        process::id(),
+        WRITES.fetch_add(1, Ordering::Relaxed)
+    ));
+    let result = fs::write(&temporary, contents)
+        .and_then(|_| match fs::metadata(&path) {
+            Ok(metadata) => fs::set_permissions(&temporary, metadata.permissions()),
+            Err(_) => Ok(()),
+        })
+        .and_then(|_| fs::rename(&temporary, &path));
+    if result.is_err() {
+        let _ = fs::remove_file(&temporary);
+    }
+    result
+}
+
+/// Inserts `entry` before any trailing blank lines so groups stay visually separated.
+fn insert_after_last_entry(output: &mut Vec<String>, entry: String) {
+    let mut index = output.len();
+    while index > 0 && output[index - 1].trim().is_empty() {
+        index -= 1;
+    }
+    output.insert(index, entry);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env::temp_dir;
+
+    #[test]
+    fn test_write_and_read_entry() {
+        let path = temp_dir().join("kswitch_test_kconfig_rc");
+        fs::write(
+            &path,
+            "[General]\nColorScheme=BreezeLight\n\n[Icons]\nTheme=breeze\n\n[KDE]\nSingleClick=false\n",
+        )
+        .unwrap();
+
+        // replace an existing key
+        write_entry(&path, &["Icons"], "Theme", "breeze-dark").unwrap();
+        // add a key to an existing group
+        write_entry(&path, &["General"], "AccentColor", "61,174,233").unwrap();
+        // add a new nested group
+        write_entry(
+            &path,
+            &["Greeter", "Wallpaper", "org.kde.image", "General"],
+            "Image",
+            "file:///tmp/a.png",
+        )
+        .unwrap();
+
+        assert_eq!(
+            read_entry(&path, &["Icons"], "Theme"),
+            Some(String::from("breeze-dark"))
+        );
+        assert_eq!(
+            read_entry(&path, &["General"], "ColorScheme"),
+            Some(String::from("BreezeLight"))
+        );
+        assert_eq!(
+            read_entry(&path, &["General"], "AccentColor"),
+            Some(String::from("61,174,233"))
+        );
+        assert_eq!(
+            read_entry(
+                &path,
+                &["Greeter", "Wallpaper", "org.kde.image", "General"],
+                "Image"
+            ),
+            Some(String::from("file:///tmp/a.png"))
+        );
+        assert_eq!(read_entry(&path, &["KDE"], "Theme"), None);
+
+        let contents = fs::read_to_string(&path).unwrap();
+        assert_eq!(
+            contents,
+            "[General]\nColorScheme=BreezeLight\nAccentColor=61,174,233\n\n[Icons]\nTheme=breeze-dark\n\n[KDE]\nSingleClick=false\n\n[Greeter][Wallpaper][org.kde.image][General]\nImage=file:///tmp/a.png\n"
+        );
+
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_write_entry_creates_file() {
+        let path = temp_dir().join("kswitch_test_kconfig_new_rc");
+        let _ = fs::remove_file(&path);
+
+        write_entry(&path, &["Icons"], "Theme", "breeze").unwrap();
+
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "[Icons]\nTheme=breeze\n"
+        );
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_write_entry_through_symlink() {
+        let dir = temp_dir().join("kswitch_test_kconfig_symlink");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let target = dir.join("dotfiles_kdeglobals");
+        let link = dir.join("kdeglobals");
+        fs::write(&target, "[Icons]\nTheme=breeze\n").unwrap();
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+
+        write_entry(&link, &["Icons"], "Theme", "breeze-dark").unwrap();
+
+        assert!(fs::symlink_metadata(&link).unwrap().is_symlink());
+        assert_eq!(
+            fs::read_to_string(&target).unwrap(),
+            "[Icons]\nTheme=breeze-dark\n"
+        );
+        // No temporary files left behind
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
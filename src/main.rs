@@ -1,20 +1,57 @@
 mod cli;
+mod command;
 mod config;
+mod daemon;
 mod get;
+mod kconfig;
 mod operations;
+mod phases;
+mod report;
 mod schedule;
+mod scripts;
 mod set;
 mod state;
 mod theme;
+mod watch;
 
 use crate::cli::{Cli, Commands};
 use crate::config::Config;
-use crate::operations::{set, toggle};
+use crate::operations::{
+    next_transition, pause_schedule, resume_schedule, schedule_at, set_manually, show_schedule,
+    status, toggle,
+};
+use crate::report::{SwitchReport, Trigger};
 use crate::theme::Theme;
 
 use clap::Parser;
 use log;
 
+/// Tell the user about components that failed or timed out while switching
+fn print_problems(report: &SwitchReport) {
+    if report.aborted {
+        println!(
+            "Error:\tSwitch to {} aborted by a failing pre-hook",
+            report.theme.to_string()
+        );
+    }
+    for problem in report.problems() {
+        println!("Warning:\t{} {}", problem.component, problem.outcome);
+    }
+    for script in report.failed_scripts() {
+        for line in script.stderr.lines() {
+            println!(
+                "Warning:\t{}: {}",
+                script
+                    .path
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy(),
+                line
+            );
+        }
+    }
+}
+
 fn main() {
     env_logger::init();
 
@@ -47,25 +84,37 @@ fn main() {
                 config_path.to_string_lossy(),
                 e
             );
-            println!(
-                "Error:\tInvalid config file at {}",
-                config_path.to_string_lossy()
-            )
+            // Still open the editor, so the config can be fixed
+            match Cli::parse().command {
+                Commands::Config {
+                    command: cli::ConfigCommand::Edit,
+                } => {
+                    log::info!("Opening invalid config for editing");
+                    let _ = config::edit(&config_path);
+                }
+                _ => println!(
+                    "Error:\tInvalid config file at {}: {}\n\tFix it with `kswitch config edit`",
+                    config_path.to_string_lossy(),
+                    e
+                ),
+            }
         }
         Ok(config) => {
             let cli = Cli::parse();
 
             match cli.command {
-                Commands::Set { theme } => {
+                Commands::Set {
+                    theme,
+                    duration,
+                    until,
+                } => {
                     log::info!("Setting theme to {}", theme.to_string());
-                    match theme {
-                        Theme::Light => {
-                            set(&theme, &config);
-                        }
-                        Theme::Dark => {
-                            set(&theme, &config);
-                        }
-                    }
+                    let until = duration.or(until);
+                    let report = match theme {
+                        Theme::Light => set_manually(&theme, &config, until),
+                        Theme::Dark => set_manually(&theme, &config, until),
+                    };
+                    print_problems(&report);
                 }
                 Commands::Config { command } => match command {
                     cli::ConfigCommand::List => {
@@ -77,9 +126,30 @@ fn main() {
                         let _ = config.edit();
                     }
                 },
+                Commands::Schedule { command } => match command {
+                    cli::ScheduleCommand::Pause { until } => pause_schedule(until),
+                    cli::ScheduleCommand::Resume => resume_schedule(),
+                    cli::ScheduleCommand::Show => show_schedule(&config),
+                    cli::ScheduleCommand::Next => next_transition(&config),
+                    cli::ScheduleCommand::At { datetime } => schedule_at(&config, &datetime),
+                },
                 Commands::Toggle => {
                     log::info!("Toggling theme");
-                    toggle(&config);
+                    let report = toggle(&config, Trigger::Toggle);
+                    print_problems(&report);
+                }
+                Commands::Status => {
+                    log::debug!("Showing status");
+                    status();
+                }
+                Commands::Daemon { follow_portal } => {
+                    log::info!("Starting daemon");
+                    let mut config = config;
+                    config.daemon.follow_portal |= follow_portal;
+                    if let Err(e) = daemon::run(&config) {
+                        log::error!("Daemon failed: {}", e);
+                        println!("Error:\tDaemon failed: {}", e);
+                    }
                 }
             }
         }
@@ -1,68 +1,50 @@
 use crate::config::Config;
-use crate::set::{color_scheme, global_theme, konsole, wallpaper};
-use crate::state::StateManager;
+use crate::phases::{self, Context};
+use crate::report::{SwitchReport, Trigger};
+use crate::state::{Override, StateManager, SwitchLock};
 use crate::theme::Theme;
-use std::process::Command;
-use std::sync::{Arc, Barrier};
-use std::thread;
+use std::sync::Arc;
+use std::time::Instant;
 
+use chrono::{Local, NaiveDateTime};
 use log;
 
+use crate::get::portal::{self as portal_preference, ColorSchemePreference};
 use crate::get::target_theme;
+use zbus::blocking::Connection;
 
-pub fn set(theme: &Theme, config: &Config) {
+pub fn set(theme: &Theme, config: &Config, trigger: Trigger) -> SwitchReport {
     log::info!("Setting theme to {}", theme.to_string());
+    let start = Instant::now();
+    // Held until the state is saved, so the daemon ignores what this switch writes
+    let _lock = SwitchLock::acquire();
 
-    let style = match theme {
-        Theme::Dark => &config.dark,
-        Theme::Light => &config.light,
+    // Scripts get told which theme they are switching away from
+    let previous = match StateManager::new().and_then(|state_manager| state_manager.load()) {
+        Ok(state) => Some(state.current_theme),
+        Err(e) => {
+            log::debug!("No previous theme available: {}", e);
+            None
+        }
     };
 
-    let global_theme = style.desktop_theme.clone();
-    let wallpaper = style.wallpaper.clone();
-    let color_scheme = style.color_scheme.clone();
-
-    log::debug!(
-        "Applying theme settings: desktop_theme={}, color_scheme={}",
-        global_theme,
-        color_scheme
-    );
-
-    // Barrier for synchronizing thread start
-    let barrier = Arc::new(Barrier::new(4)); // 3 worker threads + main
-
-    let barrier1 = Arc::clone(&barrier);
-    let theme_handle = thread::spawn(move || {
-        barrier1.wait(); // Wait until all threads are ready
-        log::debug!("Applying desktop theme: {}", global_theme);
-        let _ = global_theme::set(&global_theme);
-    });
-
-    let barrier2 = Arc::clone(&barrier);
-    let wallpaper_handle = thread::spawn(move || {
-        barrier2.wait(); // Wait until all threads are ready
-        log::debug!("Applying wallpaper: {}", wallpaper.to_string_lossy());
-        let _ = wallpaper::set(&wallpaper);
-    });
+    let context = Arc::new(Context::new(theme, previous, trigger, config));
+    let phases = phases::run(&config.phases, Arc::clone(&context));
+    let scripts = context.take_scripts();
+    let aborted = phases.iter().any(|phase| phases::aborts(phase, config));
 
-    let barrier3 = Arc::clone(&barrier);
-    let color_scheme_handle = thread::spawn(move || {
-        barrier3.wait(); // Wait until all threads are ready
-        log::debug!("Applying color scheme: {}", color_scheme);
-        let _ = color_scheme::set(&color_scheme);
-    });
-
-    barrier.wait(); // Let the threads go at the same time
-
-    // Wait for all threads to complete
-    let _ = theme_handle.join();
-    let _ = wallpaper_handle.join();
-    let _ = color_scheme_handle.join();
-
-    // apply default theme to konsole
-    // This does not need to be done in parallel as it is non-visual
-    log::debug!("Applying Konsole profile for theme: {}", theme.to_string());
-    konsole::set(&theme, &config);
+    if aborted {
+        let report = SwitchReport {
+            theme: theme.clone(),
+            trigger,
+            phases,
+            scripts,
+            duration: start.elapsed(),
+            aborted,
+        };
+        log::warn!("{}", report);
+        return report;
+    }
 
     // Save the theme state to file
     log::debug!("Saving theme state: {}", theme.to_string());
@@ -80,89 +62,54 @@ pub fn set(theme: &Theme, config: &Config) {
         }
     }
 
-    // Run user scripts for the theme
-    run_theme_scripts(theme, config);
-
-    log::info!("Theme successfully set to {}", theme.to_string());
-}
-
-fn run_theme_scripts(theme: &Theme, config: &Config) {
-    let scripts_dir = match theme {
-        Theme::Dark => &config.dark_scripts_dir,
-        Theme::Light => &config.light_scripts_dir,
+    let report = SwitchReport {
+        theme: theme.clone(),
+        trigger,
+        phases,
+        scripts,
+        duration: start.elapsed(),
+        aborted,
     };
+    log::info!("{}", report);
+    log::info!("Theme set to {}", theme.to_string());
+    report
+}
 
-    log::debug!(
-        "Looking for {} theme scripts in {}",
-        theme.to_string(),
-        scripts_dir.to_string_lossy()
-    );
-
-    // Check if directory exists
-    if !scripts_dir.is_dir() {
-        log::debug!(
-            "Scripts directory does not exist: {}",
-            scripts_dir.to_string_lossy()
-        );
-        return;
-    }
-
-    // Read all files in the scripts directory
-    match std::fs::read_dir(scripts_dir) {
-        Ok(entries) => {
-            let script_files: Vec<_> = entries
-                .filter_map(|entry| {
-                    entry.ok().and_then(|e| {
-                        let path = e.path();
-                        if path.is_file() { Some(path) } else { None }
-                    })
-                })
-                .collect();
-
-            if script_files.is_empty() {
-                log::debug!(
-                    "No scripts found in directory: {}",
-                    scripts_dir.to_string_lossy()
-                );
-                return;
-            }
+/// Remember how long a manually chosen theme holds against the schedule. `None` drops an
+/// earlier override, so the schedule takes over again at its next window.
+fn save_override(theme: &Theme, until: Option<NaiveDateTime>) {
+    let manual_override = until.map(|until| Override {
+        theme: theme.clone(),
+        until,
+    });
+    match StateManager::new().and_then(|state_manager| {
+        state_manager.update(|state| state.manual_override = manual_override)
+    }) {
+        Ok(_) => match until {
+            Some(until) => log::info!(
+                "Holding {} until {}",
+                theme.to_string(),
+                until.format("%Y-%m-%d %H:%M")
+            ),
+            None => log::debug!("Cleared manual override"),
+        },
+        Err(e) => log::warn!("Failed to save manual override: {}", e),
+    }
+}
 
-            log::info!("Found {} theme script(s) to execute", script_files.len());
-
-            for path in script_files {
-                log::info!("Running script: {}", path.to_string_lossy());
-                match Command::new("sh")
-                    .arg("-c")
-                    .arg(format!("source {}", &path.display()))
-                    .status()
-                {
-                    Ok(status) => {
-                        if status.success() {
-                            log::info!("Script executed successfully: {}", path.to_string_lossy());
-                        } else {
-                            log::warn!(
-                                "Script exited with non-zero status: {}",
-                                path.to_string_lossy()
-                            );
-                        }
-                    }
-                    Err(e) => {
-                        log::error!("Failed to execute script {}: {}", path.to_string_lossy(), e);
-                    }
-                }
-            }
-        }
-        Err(e) => {
-            log::error!(
-                "Failed to read scripts directory {}: {}",
-                scripts_dir.to_string_lossy(),
-                e
-            );
-        }
+/// Set the theme from the command line, optionally holding it against the schedule `until` a
+/// given time.
+pub fn set_manually(theme: &Theme, config: &Config, until: Option<NaiveDateTime>) -> SwitchReport {
+    let report = set(theme, config, Trigger::Set);
+    if !report.aborted {
+        save_override(theme, until);
     }
+    report
 }
 
-pub fn toggle(config: &Config) {
+/// Switch to the other theme, ending any manual override. Used by the command line and the
+/// daemon's D-Bus `Toggle` alike.
+pub fn toggle(config: &Config, trigger: Trigger) -> SwitchReport {
     log::info!("Toggling theme");
     let target_theme = target_theme::get(config);
 
@@ -177,7 +124,143 @@ pub fn toggle(config: &Config) {
     );
 
     // set to target theme
-    set(&target_theme, &config);
+    let report = set(&target_theme, &config, trigger);
+    if !report.aborted {
+        save_override(&target_theme, None);
+    }
+    report
+}
+
+/// Stop the daemon from switching on schedule, until `until` or until resumed.
+pub fn pause_schedule(until: Option<NaiveDateTime>) {
+    log::info!("Pausing the schedule");
+    let result = StateManager::new().and_then(|state_manager| {
+        state_manager.update(|state| {
+            state.schedule_paused = true;
+            state.paused_until = until;
+        })
+    });
+    match (result, until) {
+        (Err(e), _) => {
+            log::error!("Failed to pause the schedule: {}", e);
+            println!("Error:\tFailed to pause the schedule: {}", e);
+        }
+        (Ok(_), Some(until)) => {
+            println!("Schedule paused until {}", until.format("%Y-%m-%d %H:%M"))
+        }
+        (Ok(_), None) => println!("Schedule paused"),
+    }
+}
+
+/// Let the daemon follow the schedule again. It applies the current window right away.
+pub fn resume_schedule() {
+    log::info!("Resuming the schedule");
+    let result = StateManager::new().and_then(|state_manager| {
+        state_manager.update(|state| {
+            state.schedule_paused = false;
+            state.paused_until = None;
+        })
+    });
+    match result {
+        Ok(_) => println!("Schedule resumed"),
+        Err(e) => {
+            log::error!("Failed to resume the schedule: {}", e);
+            println!("Error:\tFailed to resume the schedule: {}", e);
+        }
+    }
+}
+
+/// Print the schedule windows in the order they start.
+pub fn show_schedule(config: &Config) {
+    log::info!("Showing schedule");
+    for window in config.schedule.ordered() {
+        println!("{}", window);
+    }
+}
+
+/// Print when the schedule next switches theme.
+pub fn next_transition(config: &Config) {
+    let now = Local::now().naive_local();
+    log::info!("Finding next schedule transition");
+    match config.schedule.next_transition(&now) {
+        Some((start, theme)) => println!(
+            "Next:\t{} at {}",
+            theme.to_string(),
+            start.format("%Y-%m-%d %H:%M")
+        ),
+        None => println!("Next:\tno transition within a year"),
+    }
+}
+
+/// Print the theme the schedule picks at `datetime`.
+pub fn schedule_at(config: &Config, datetime: &NaiveDateTime) {
+    log::info!(
+        "Evaluating schedule at {}",
+        datetime.format("%Y-%m-%d %H:%M")
+    );
+    match config.schedule.theme_from_time(datetime) {
+        Ok(theme) => println!(
+            "Theme:\t{} at {}",
+            theme.to_string(),
+            datetime.format("%Y-%m-%d %H:%M")
+        ),
+        Err(e) => {
+            log::error!("Failed to evaluate the schedule: {}", e);
+            println!("Error:\t{}", e);
+        }
+    }
+}
+
+/// Print the current theme and whether the portal color-scheme preference agrees with it.
+pub fn status() {
+    log::info!("Reporting status");
+
+    let state = match StateManager::new().and_then(|state_manager| state_manager.load()) {
+        Ok(state) => state,
+        Err(e) => {
+            log::error!("Failed to load theme state: {}", e);
+            println!("Error:\tFailed to load theme state: {}", e);
+            return;
+        }
+    };
+    let theme = state.current_theme.clone();
+    println!("Theme:\t{}", theme.to_string());
+    let now = Local::now().naive_local();
+    if state.is_paused(&now) {
+        match state.paused_until {
+            Some(until) => println!("Schedule:\tpaused until {}", until.format("%Y-%m-%d %H:%M")),
+            None => println!("Schedule:\tpaused"),
+        }
+    }
+    if let Some(manual_override) = state.active_override(&now) {
+        println!(
+            "Override:\t{} until {}",
+            manual_override.theme.to_string(),
+            manual_override.until.format("%Y-%m-%d %H:%M")
+        );
+    }
+
+    let expected = ColorSchemePreference::for_theme(&theme);
+    match Connection::session().and_then(|connection| portal_preference::read(&connection)) {
+        Ok(preference) => {
+            println!("Portal:\t{}", preference);
+            if preference != expected {
+                log::warn!(
+                    "Portal color-scheme {} does not match theme {}",
+                    preference,
+                    theme.to_string()
+                );
+                println!(
+                    "Warning:\tPortal color-scheme is {}, expected {}",
+                    preference, expected
+                );
+            }
+        }
+        Err(e) => {
+            log::warn!("Failed to read portal color-scheme: {}", e);
+            println!("Portal:\tunavailable ({})", e);
+        }
+    }
 }
 
 #[cfg(test)]
@@ -188,19 +271,19 @@ mod tests {
     fn test_set_light() {
         let config = Config::default();
         let theme = Theme::Light;
-        set(&theme, &config);
+        set(&theme, &config, Trigger::Set);
     }
 
     #[test]
     fn test_set_dark() {
         let config = Config::default();
         let theme = Theme::Dark;
-        set(&theme, &config);
+        set(&theme, &config, Trigger::Set);
     }
 
     #[test]
     fn test_toggle() {
         let config = Config::default();
-        toggle(&config);
+        toggle(&config, Trigger::Toggle);
     }
 }
@@ -0,0 +1,786 @@
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::Path;
+use std::process::Output;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Barrier, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::command;
+use crate::config::Config;
+use crate::report::{ComponentReport, Outcome, PhaseReport, ScriptReport, Trigger};
+use crate::scripts::{self, Environment, Hook};
+use crate::set::wallpaper::{self, Prepared};
+use crate::set::{
+    app_color_scheme, bat, color_scheme, cursor_theme, delta, fzf, global_theme, gtk, icon_theme,
+    konsole, lockscreen, neovim, portal, tmux, vscode, window_decoration,
+};
+use crate::theme::{Style, Theme};
+
+/// How long past the phase timeout to wait for components whose commands were killed at it
+const KILL_GRACE: Duration = Duration::from_millis(500);
+
+/// Everything kswitch can apply during a switch
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum Component {
+    /// Pre-hook scripts, see [`crate::scripts`]
+    PreScripts,
+    /// Resolve, validate and read the wallpaper ahead of the visual switch
+    WallpaperPreload,
+    Wallpaper,
+    ColorScheme,
+    DesktopTheme,
+    IconTheme,
+    CursorTheme,
+    Gtk,
+    WindowDecoration,
+    Konsole,
+    Neovim,
+    Vscode,
+    Tmux,
+    Bat,
+    Delta,
+    Fzf,
+    AppColorSchemes,
+    LockscreenWallpaper,
+    Portal,
+    Scripts,
+}
+
+impl fmt::Display for Component {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Component::PreScripts => "pre_scripts",
+            Component::WallpaperPreload => "wallpaper_preload",
+            Component::Wallpaper => "wallpaper",
+            Component::ColorScheme => "color_scheme",
+            Component::DesktopTheme => "desktop_theme",
+            Component::IconTheme => "icon_theme",
+            Component::CursorTheme => "cursor_theme",
+            Component::Gtk => "gtk",
+            Component::WindowDecoration => "window_decoration",
+            Component::Konsole => "konsole",
+            Component::Neovim => "neovim",
+            Component::Vscode => "vscode",
+            Component::Tmux => "tmux",
+            Component::Bat => "bat",
+            Component::Delta => "delta",
+            Component::Fzf => "fzf",
+            Component::AppColorSchemes => "app_color_schemes",
+            Component::LockscreenWallpaper => "lockscreen_wallpaper",
+            Component::Portal => "portal",
+            Component::Scripts => "scripts",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl Component {
+    /// Whether the component runs an external command, which `timeouts` can limit. The others
+    /// talk D-Bus or write files and are only bounded by their phase timeout.
+    pub fn runs_command(&self) -> bool {
+        matches!(
+            self,
+            Component::ColorScheme
+                | Component::DesktopTheme
+                | Component::CursorTheme
+                | Component::Gtk
+                | Component::Neovim
+                | Component::Tmux
+                | Component::Delta
+        )
+    }
+}
+
+/// Components started together; the next phase starts once all of them finished or the
+/// timeout (in seconds) elapsed.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct Phase {
+    pub components: Vec<Component>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
+}
+
+/// Run the pre-hooks, preload the wallpaper, switch everything visible at once, then the rest.
+/// The icon theme goes with the rest, as it writes kdeglobals which `plasma-apply-colorscheme`
+/// rewrites too.
+pub fn default_phases() -> Vec<Phase> {
+    vec![
+        Phase {
+            components: vec![Component::PreScripts],
+            timeout: Some(60),
+        },
+        Phase {
+            components: vec![Component::WallpaperPreload],
+            timeout: Some(5),
+        },
+        Phase {
+            components: vec![
+                Component::Wallpaper,
+                Component::ColorScheme,
+                Component::DesktopTheme,
+                Component::CursorTheme,
+                Component::Gtk,
+                Component::WindowDecoration,
+            ],
+            timeout: Some(10),
+        },
+        Phase {
+            components: vec![
+                Component::IconTheme,
+                Component::Konsole,
+                Component::Neovim,
+                Component::Vscode,
+                Component::Tmux,
+                Component::Bat,
+                Component::Delta,
+                Component::Fzf,
+                Component::AppColorSchemes,
+                Component::LockscreenWallpaper,
+                Component::Portal,
+            ],
+            timeout: Some(10),
+        },
+        Phase {
+            components: vec![Component::Scripts],
+            timeout: Some(60),
+        },
+    ]
+}
+
+/// Whether `phases` are the defaults. Those are left out of a saved config, so that components
+/// added in a later release are applied without the user listing them.
+pub fn is_default_phases(phases: &[Phase]) -> bool {
+    *phases == default_phases()
+}
+
+fn default_command_timeout() -> u64 {
+    10
+}
+
+fn default_script_timeout() -> u64 {
+    30
+}
+
+/// Reject limits for components that run no command, as they would be silently ignored
+fn deserialize_component_timeouts<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<BTreeMap<Component, u64>, D::Error> {
+    let components = BTreeMap::<Component, u64>::deserialize(deserializer)?;
+    match components
+        .keys()
+        .find(|component| !component.runs_command())
+    {
+        Some(component) => Err(D::Error::custom(format!(
+            "{} runs no external command and cannot have a timeout, use the timeout of its phase",
+            component
+        ))),
+        None => Ok(components),
+    }
+}
+
+/// Time limits (in seconds) for the external commands run while applying a theme.
+/// Commands still running at their limit are killed.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Timeouts {
+    /// Limit for the command of any component not listed in `components`
+    #[serde(default = "default_command_timeout")]
+    pub command: u64,
+    /// Limits for components that run a command, see [`Component::runs_command`]
+    #[serde(
+        default,
+        skip_serializing_if = "BTreeMap::is_empty",
+        deserialize_with = "deserialize_component_timeouts"
+    )]
+    pub components: BTreeMap<Component, u64>,
+    /// Limit for any script not listed in `scripts`
+    #[serde(default = "default_script_timeout")]
+    pub script: u64,
+    /// Limits keyed by script file name
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub scripts: BTreeMap<String, u64>,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Timeouts {
+            command: default_command_timeout(),
+            components: BTreeMap::new(),
+            script: default_script_timeout(),
+            scripts: BTreeMap::new(),
+        }
+    }
+}
+
+impl Timeouts {
+    pub fn for_component(&self, component: &Component) -> Duration {
+        Duration::from_secs(*self.components.get(component).unwrap_or(&self.command))
+    }
+
+    pub fn for_script(&self, script: &Path) -> Duration {
+        let name = script
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        Duration::from_secs(*self.scripts.get(&name).unwrap_or(&self.script))
+    }
+}
+
+/// State shared by the component threads of a switch
+pub struct Context {
+    pub theme: Theme,
+    /// Theme before the switch, if known
+    pub previous: Option<Theme>,
+    pub trigger: Trigger,
+    pub config: Config,
+    /// Wallpaper prepared by the preload phase, consumed by the wallpaper component
+    wallpaper: Mutex<Option<Prepared>>,
+    /// Reports of the scripts run so far
+    scripts: Mutex<Vec<ScriptReport>>,
+    /// End of the running phase, which commands and scripts are killed by at the latest
+    deadline: Mutex<Option<Instant>>,
+}
+
+impl Context {
+    pub fn new(theme: &Theme, previous: Option<Theme>, trigger: Trigger, config: &Config) -> Self {
+        Context {
+            theme: theme.clone(),
+            previous,
+            trigger,
+            config: config.clone(),
+            wallpaper: Mutex::new(None),
+            scripts: Mutex::new(Vec::new()),
+            deadline: Mutex::new(None),
+        }
+    }
+
+    fn deadline(&self) -> Option<Instant> {
+        self.deadline.lock().ok().and_then(|deadline| *deadline)
+    }
+
+    /// Time limit for the commands of a component, ending with the running phase at the latest
+    fn timeout(&self, component: &Component) -> Duration {
+        command::within(
+            self.config.timeouts.for_component(component),
+            self.deadline(),
+        )
+    }
+
+    /// Take the reports of the scripts run so far
+    pub fn take_scripts(&self) -> Vec<ScriptReport> {
+        self.scripts
+            .lock()
+            .map(|mut scripts| std::mem::take(&mut *scripts))
+            .unwrap_or_default()
+    }
+
+    /// Run the scripts of a hook and keep their reports for the switch report.
+    fn run_scripts(&self, hook: Hook) -> Outcome {
+        let reports = scripts::run(&self.environment(hook), &self.config);
+        let outcome = scripts::outcome(&reports);
+        if let Ok(mut scripts) = self.scripts.lock() {
+            scripts.extend(reports);
+        }
+        outcome
+    }
+
+    fn environment(&self, hook: Hook) -> Environment<'_> {
+        Environment {
+            theme: &self.theme,
+            previous: self.previous.as_ref(),
+            trigger: &self.trigger,
+            hook,
+            deadline: self.deadline(),
+        }
+    }
+
+    fn style(&self) -> &Style {
+        match self.theme {
+            Theme::Dark => &self.config.dark,
+            Theme::Light => &self.config.light,
+        }
+    }
+}
+
+fn from_output(result: io::Result<Output>) -> Outcome {
+    match result {
+        Err(e) if e.kind() == io::ErrorKind::TimedOut => Outcome::TimedOut,
+        Ok(output) if output.status.success() => Outcome::Applied,
+        Ok(output) => Outcome::Failed(format!(
+            "{}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )),
+        Err(e) => Outcome::Failed(e.to_string()),
+    }
+}
+
+fn from_result<E: fmt::Display>(result: Result<(), E>) -> Outcome {
+    match result {
+        Ok(_) => Outcome::Applied,
+        Err(e) => Outcome::Failed(e.to_string()),
+    }
+}
+
+fn from_io(result: io::Result<()>) -> Outcome {
+    match result {
+        Err(e) if e.kind() == io::ErrorKind::TimedOut => Outcome::TimedOut,
+        result => from_result(result),
+    }
+}
+
+fn apply(component: &Component, context: &Context) -> Outcome {
+    let theme = &context.theme;
+    let style = context.style();
+    let timeout = context.timeout(component);
+
+    match component {
+        Component::PreScripts => context.run_scripts(Hook::Pre),
+        Component::WallpaperPreload => match wallpaper::prepare(&style.wallpaper, theme) {
+            Ok(prepared) => {
+                // Hand the resolved wallpaper over before reading it, so the wallpaper
+                // component uses it even if preloading runs past the phase timeout. Resolving
+                // again would move round-robin on twice.
+                if let Ok(mut slot) = context.wallpaper.lock() {
+                    *slot = Some(prepared.clone());
+                }
+                prepared.preload();
+                Outcome::Applied
+            }
+            Err(e) => Outcome::Failed(e.to_string()),
+        },
+        Component::Wallpaper => {
+            let prepared = context
+                .wallpaper
+                .lock()
+                .ok()
+                .and_then(|mut slot| slot.take());
+            match prepared {
+                Some(prepared) => from_output(wallpaper::apply(&prepared)),
+                None => from_output(wallpaper::set(&style.wallpaper, theme)),
+            }
+        }
+        Component::ColorScheme => from_output(color_scheme::set(&style.color_scheme, timeout)),
+        Component::DesktopTheme => from_output(global_theme::set(&style.desktop_theme, timeout)),
+        Component::IconTheme => match &style.icon_theme {
+            Some(icon_theme) => from_result(icon_theme::set(icon_theme)),
+            None => Outcome::Skipped,
+        },
+        Component::CursorTheme => match &style.cursor_theme {
+            Some(cursor_theme) => {
+                from_output(cursor_theme::set(cursor_theme, style.cursor_size, timeout))
+            }
+            None => Outcome::Skipped,
+        },
+        Component::Gtk => {
+            let options = &context.config.gtk;
+            if style.gtk_theme.is_none() && !options.sync_color_scheme {
+                return Outcome::Skipped;
+            }
+            from_result(gtk::set(theme, style.gtk_theme.as_ref(), options, timeout))
+        }
+        Component::WindowDecoration => match &style.window_decoration {
+            Some(decoration) => from_result(window_decoration::set(decoration)),
+            None => Outcome::Skipped,
+        },
+        Component::Konsole => {
+            konsole::set(theme, &context.config);
+            Outcome::Applied
+        }
+        Component::Neovim => match &style.neovim {
+            Some(neovim) => from_result(neovim::set(theme, neovim, timeout)),
+            None => Outcome::Skipped,
+        },
+        Component::Vscode => match &style.vscode_theme {
+            Some(vscode_theme) => from_result(vscode::set(vscode_theme)),
+            None => Outcome::Skipped,
+        },
+        Component::Tmux => match &style.tmux_theme {
+            Some(tmux_theme) => from_result(tmux::set(tmux_theme, timeout)),
+            None => Outcome::Skipped,
+        },
+        Component::Bat => match &style.bat_theme {
+            Some(bat_theme) => from_result(bat::set(bat_theme)),
+            None => Outcome::Skipped,
+        },
+        Component::Delta => match &style.delta_theme {
+            Some(delta_theme) => from_result(delta::set(theme, delta_theme, timeout)),
+            None => Outcome::Skipped,
+        },
+        Component::Fzf => match &style.fzf_colors {
+            Some(fzf_colors) => from_result(fzf::set(fzf_colors)),
+            None => Outcome::Skipped,
+        },
+        Component::AppColorSchemes => match style.app_color_schemes.is_empty() {
+            true => Outcome::Skipped,
+            false => from_result(app_color_scheme::set(&style.app_color_schemes)),
+        },
+        Component::LockscreenWallpaper => match &style.lockscreen_wallpaper {
+            Some(lockscreen_wallpaper) => {
+                from_result::<Box<dyn Error>>(lockscreen::set(lockscreen_wallpaper))
+            }
+            None => Outcome::Skipped,
+        },
+        Component::Portal => from_io(portal::set(theme)),
+        Component::Scripts => context.run_scripts(Hook::Post),
+    }
+}
+
+/// Start all components of a phase at once and wait for them until the phase timeout.
+///
+/// Commands and scripts the components run are killed at the timeout at the latest, and the
+/// components get [`KILL_GRACE`] to report that. Components still running after it, e.g. on a
+/// D-Bus call, are reported as timed out and not waited for. Their threads keep going in the
+/// daemon, but a `kswitch` command exits right after the switch, which stops them wherever
+/// they are, possibly in the middle of writing a file.
+fn run_phase(
+    number: usize,
+    phase: &Phase,
+    context: &Arc<Context>,
+    apply: fn(&Component, &Context) -> Outcome,
+) -> PhaseReport {
+    log::debug!("Starting phase {}: {:?}", number, phase.components);
+    let start = Instant::now();
+    let deadline = phase.timeout.map(|t| start + Duration::from_secs(t));
+    if let Ok(mut slot) = context.deadline.lock() {
+        *slot = deadline;
+    }
+    let (sender, receiver) = mpsc::channel();
+
+    // Barrier for synchronizing thread start
+    let barrier = Arc::new(Barrier::new(phase.components.len() + 1));
+
+    for component in phase.components.iter().copied() {
+        let barrier = Arc::clone(&barrier);
+        let context = Arc::clone(context);
+        let sender = sender.clone();
+        thread::spawn(move || {
+            barrier.wait(); // Wait until all threads are ready
+            let started = Instant::now();
+            let outcome = apply(&component, &context);
+            let _ = sender.send(ComponentReport {
+                component,
+                outcome,
+                duration: started.elapsed(),
+            });
+        });
+    }
+    drop(sender);
+
+    barrier.wait(); // Let the threads go at the same time
+
+    let mut reports: Vec<ComponentReport> = Vec::new();
+    let mut disconnected = false;
+    while reports.len() < phase.components.len() {
+        let received = match deadline {
+            Some(deadline) => receiver
+                .recv_timeout((deadline + KILL_GRACE).saturating_duration_since(Instant::now())),
+            None => receiver.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+        match received {
+            Ok(report) => {
+                log::info!(
+                    "{} {} in {} ms",
+                    report.component,
+                    report.outcome,
+                    report.duration.as_millis()
+                );
+                reports.push(report);
+            }
+            Err(RecvTimeoutError::Timeout) => break,
+            Err(RecvTimeoutError::Disconnected) => {
+                disconnected = true;
+                break;
+            }
+        }
+    }
+
+    // Whatever did not report either panicked or is still running
+    let mut ordered = Vec::new();
+    for component in phase.components.iter() {
+        let report = match reports.iter().position(|r| r.component == *component) {
+            Some(index) => reports.remove(index),
+            None => {
+                let outcome = match disconnected {
+                    true => Outcome::Failed(String::from("component panicked")),
+                    false => Outcome::TimedOut,
+                };
+                log::warn!("{} {} in phase {}", component, outcome, number);
+                ComponentReport {
+                    component: *component,
+                    outcome,
+                    duration: start.elapsed(),
+                }
+            }
+        };
+        ordered.push(report);
+    }
+
+    let duration = start.elapsed();
+    log::info!("Phase {} finished in {} ms", number, duration.as_millis());
+    PhaseReport {
+        components: ordered,
+        duration,
+    }
+}
+
+/// The phases without the given components, dropping phases left empty.
+pub fn without(phases: &[Phase], components: &[Component]) -> Vec<Phase> {
+    phases
+        .iter()
+        .map(|phase| Phase {
+            components: phase
+                .components
+                .iter()
+                .filter(|c| !components.contains(c))
+                .copied()
+                .collect(),
+            timeout: phase.timeout,
+        })
+        .filter(|phase| !phase.components.is_empty())
+        .collect()
+}
+
+/// Whether the phase had a failing pre-hook that should stop the switch
+pub fn aborts(phase: &PhaseReport, config: &Config) -> bool {
+    config.scripts.abort_on_pre_failure
+        && phase
+            .components
+            .iter()
+            .any(|c| c.component == Component::PreScripts && c.outcome.is_problem())
+}
+
+/// Run the phases one after another, stopping early if a pre-hook aborts the switch.
+pub fn run(phases: &[Phase], context: Arc<Context>) -> Vec<PhaseReport> {
+    let mut reports = Vec::new();
+    for (i, phase) in phases.iter().enumerate() {
+        let report = run_phase(i + 1, phase, &context, apply);
+        let abort = aborts(&report, &context.config);
+        reports.push(report);
+        if abort {
+            log::warn!("Pre-hook failed, aborting switch after phase {}", i + 1);
+            break;
+        }
+    }
+    reports
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_phases_cover_every_component_once() {
+        let components: Vec<Component> = default_phases()
+            .into_iter()
+            .flat_map(|phase| phase.components)
+            .collect();
+
+        for component in [
+            Component::PreScripts,
+            Component::WallpaperPreload,
+            Component::Wallpaper,
+            Component::ColorScheme,
+            Component::DesktopTheme,
+            Component::IconTheme,
+            Component::CursorTheme,
+            Component::Gtk,
+            Component::WindowDecoration,
+            Component::Konsole,
+            Component::Neovim,
+            Component::Vscode,
+            Component::Tmux,
+            Component::Bat,
+            Component::Delta,
+            Component::Fzf,
+            Component::AppColorSchemes,
+            Component::LockscreenWallpaper,
+            Component::Portal,
+            Component::Scripts,
+        ] {
+            assert_eq!(
+                components.iter().filter(|c| **c == component).count(),
+                1,
+                "{} should appear exactly once",
+                component
+            );
+        }
+    }
+
+    /// Finishes the wallpaper instantly, never finishes the scripts in time
+    fn slow_scripts(component: &Component, _context: &Context) -> Outcome {
+        match component {
+            Component::Scripts => {
+                thread::sleep(Duration::from_secs(3));
+                Outcome::Applied
+            }
+            Component::Wallpaper => Outcome::Applied,
+            _ => Outcome::Failed(String::from("boom")),
+        }
+    }
+
+    #[test]
+    fn test_run_phase_times_out() {
+        let context = Arc::new(Context::new(
+            &Theme::Dark,
+            Some(Theme::Light),
+            Trigger::Toggle,
+            &Config::default(),
+        ));
+        let phase = Phase {
+            components: vec![Component::Scripts, Component::Wallpaper, Component::Gtk],
+            timeout: Some(1),
+        };
+
+        let report = run_phase(1, &phase, &context, slow_scripts);
+
+        let outcomes: Vec<(Component, Outcome)> = report
+            .components
+            .iter()
+            .map(|c| (c.component, c.outcome.clone()))
+            .collect();
+        assert_eq!(
+            outcomes,
+            vec![
+                (Component::Scripts, Outcome::TimedOut),
+                (Component::Wallpaper, Outcome::Applied),
+                (Component::Gtk, Outcome::Failed(String::from("boom"))),
+            ]
+        );
+        assert!(report.duration < Duration::from_secs(2));
+    }
+
+    /// Runs a command that outlives both the phase timeout and the grace after it
+    fn slow_command(_component: &Component, context: &Context) -> Outcome {
+        let pid_file = std::env::temp_dir().join("kswitch_test_phase_command.pid");
+        let script = format!("echo $$ > '{}'; exec sleep 30", pid_file.display());
+        from_output(command::output(
+            std::process::Command::new("sh").arg("-c").arg(script),
+            context.timeout(&Component::Tmux),
+        ))
+    }
+
+    #[test]
+    fn test_run_phase_kills_commands_at_timeout() {
+        let pid_file = std::env::temp_dir().join("kswitch_test_phase_command.pid");
+        let _ = std::fs::remove_file(&pid_file);
+        let context = Arc::new(Context::new(
+            &Theme::Dark,
+            None,
+            Trigger::Toggle,
+            &Config::default(),
+        ));
+        let phase = Phase {
+            components: vec![Component::Tmux],
+            timeout: Some(1),
+        };
+
+        let report = run_phase(1, &phase, &context, slow_command);
+
+        assert_eq!(report.components[0].outcome, Outcome::TimedOut);
+        assert!(report.components[0].duration < Duration::from_secs(1) + KILL_GRACE);
+        let pid = std::fs::read_to_string(&pid_file).unwrap();
+        let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid.trim()));
+        assert!(stat.is_err() || stat.is_ok_and(|stat| stat.contains(") Z ")));
+
+        let _ = std::fs::remove_file(&pid_file);
+    }
+
+    #[test]
+    fn test_from_io() {
+        assert_eq!(from_io(Ok(())), Outcome::Applied);
+        assert_eq!(
+            from_io(Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "still prefer-light"
+            ))),
+            Outcome::TimedOut
+        );
+        assert_eq!(
+            from_io(Err(io::Error::other("no session bus"))),
+            Outcome::Failed(String::from("no session bus"))
+        );
+    }
+
+    #[test]
+    fn test_without() {
+        let phases = without(
+            &default_phases(),
+            &[Component::PreScripts, Component::ColorScheme],
+        );
+
+        assert_eq!(phases.len(), default_phases().len() - 1);
+        assert_eq!(phases[0].components, vec![Component::WallpaperPreload]);
+        assert!(!phases[1].components.contains(&Component::ColorScheme));
+        assert!(phases[1].components.contains(&Component::DesktopTheme));
+    }
+
+    #[test]
+    fn test_aborts_on_failing_pre_hook() {
+        let phase = PhaseReport {
+            components: vec![ComponentReport {
+                component: Component::PreScripts,
+                outcome: Outcome::Failed(String::from("10-check exited with 1")),
+                duration: Duration::from_millis(5),
+            }],
+            duration: Duration::from_millis(5),
+        };
+        let mut config = Config::default();
+
+        assert!(!aborts(&phase, &config));
+        config.scripts.abort_on_pre_failure = true;
+        assert!(aborts(&phase, &config));
+    }
+
+    #[test]
+    fn test_timeouts_from_toml() {
+        let timeouts: Timeouts = toml::from_str(
+            "command = 5\n[components]\ncolor_scheme = 2\n[scripts]\n\"slow.sh\" = 120",
+        )
+        .unwrap();
+
+        assert_eq!(
+            timeouts.for_component(&Component::ColorScheme),
+            Duration::from_secs(2)
+        );
+        assert_eq!(
+            timeouts.for_component(&Component::DesktopTheme),
+            Duration::from_secs(5)
+        );
+        assert_eq!(
+            timeouts.for_script(Path::new("/home/me/.config/kswitch/dark/slow.sh")),
+            Duration::from_secs(120)
+        );
+        assert_eq!(
+            timeouts.for_script(Path::new("/home/me/.config/kswitch/dark/fast.sh")),
+            Duration::from_secs(30)
+        );
+    }
+
+    #[test]
+    fn test_timeouts_reject_components_without_command() {
+        let error = toml::from_str::<Timeouts>("[components]\nwallpaper = 2")
+            .unwrap_err()
+            .to_string();
+
+        assert!(error.contains("wallpaper runs no external command"));
+    }
+
+    #[test]
+    fn test_phase_from_toml() {
+        let phase: Phase =
+            toml::from_str("components = [\"wallpaper\", \"color_scheme\"]\ntimeout = 3").unwrap();
+
+        assert_eq!(
+            phase.components,
+            vec![Component::Wallpaper, Component::ColorScheme]
+        );
+        assert_eq!(phase.timeout, Some(3));
+    }
+}
@@ -0,0 +1,165 @@
+use std::fmt;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::phases::Component;
+use crate::scripts::Hook;
+use crate::theme::Theme;
+
+/// What started a switch, passed on to scripts
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Trigger {
+    Set,
+    Toggle,
+    /// The daemon followed the schedule
+    Schedule,
+    /// A call to the daemon's D-Bus interface
+    DBus,
+    /// The theme was changed outside kswitch, e.g. in System Settings
+    External,
+    /// The settings portal color-scheme changed
+    Portal,
+}
+
+impl fmt::Display for Trigger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Trigger::Set => write!(f, "set"),
+            Trigger::Toggle => write!(f, "toggle"),
+            Trigger::Schedule => write!(f, "schedule"),
+            Trigger::DBus => write!(f, "dbus"),
+            Trigger::External => write!(f, "external"),
+            Trigger::Portal => write!(f, "portal"),
+        }
+    }
+}
+
+/// What happened to a component during a switch
+#[derive(Debug, PartialEq, Clone)]
+pub enum Outcome {
+    Applied,
+    /// Not configured for this style
+    Skipped,
+    Failed(String),
+    /// Still running when its phase timed out
+    TimedOut,
+}
+
+impl Outcome {
+    pub fn is_problem(&self) -> bool {
+        matches!(self, Outcome::Failed(_) | Outcome::TimedOut)
+    }
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Outcome::Applied => write!(f, "applied"),
+            Outcome::Skipped => write!(f, "skipped"),
+            Outcome::Failed(e) => write!(f, "failed: {}", e),
+            Outcome::TimedOut => write!(f, "timed out"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ComponentReport {
+    pub component: Component,
+    pub outcome: Outcome,
+    /// Time until the component finished, or until its phase gave up on it
+    pub duration: Duration,
+}
+
+/// A single script run, with its captured output
+#[derive(Debug, Clone)]
+pub struct ScriptReport {
+    pub path: PathBuf,
+    pub hook: Hook,
+    pub outcome: Outcome,
+    pub duration: Duration,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct PhaseReport {
+    pub components: Vec<ComponentReport>,
+    pub duration: Duration,
+}
+
+/// Result of applying a theme, phase by phase
+#[derive(Debug, Clone)]
+pub struct SwitchReport {
+    pub theme: Theme,
+    pub trigger: Trigger,
+    pub phases: Vec<PhaseReport>,
+    /// Scripts that finished, in the order they finished
+    pub scripts: Vec<ScriptReport>,
+    pub duration: Duration,
+    /// A failing pre-hook stopped the switch before anything was applied
+    pub aborted: bool,
+}
+
+impl SwitchReport {
+    /// Components that failed or timed out
+    pub fn problems(&self) -> Vec<&ComponentReport> {
+        self.phases
+            .iter()
+            .flat_map(|phase| phase.components.iter())
+            .filter(|c| c.outcome.is_problem())
+            .collect()
+    }
+
+    /// Scripts that failed or timed out
+    pub fn failed_scripts(&self) -> Vec<&ScriptReport> {
+        self.scripts
+            .iter()
+            .filter(|s| s.outcome.is_problem())
+            .collect()
+    }
+}
+
+impl fmt::Display for SwitchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} to {} ({}) in {} ms",
+            if self.aborted {
+                "Aborted switch"
+            } else {
+                "Switched"
+            },
+            self.theme.to_string(),
+            self.trigger,
+            self.duration.as_millis()
+        )?;
+        for (i, phase) in self.phases.iter().enumerate() {
+            writeln!(f, "  phase {} ({} ms)", i + 1, phase.duration.as_millis())?;
+            for c in phase.components.iter() {
+                writeln!(
+                    f,
+                    "    {}: {} ({} ms)",
+                    c.component,
+                    c.outcome,
+                    c.duration.as_millis()
+                )?;
+            }
+        }
+        for script in self.scripts.iter() {
+            writeln!(
+                f,
+                "  {}-hook {}: {} ({} ms)",
+                script.hook,
+                script.path.to_string_lossy(),
+                script.outcome,
+                script.duration.as_millis()
+            )?;
+            for (stream, output) in [("stdout", &script.stdout), ("stderr", &script.stderr)] {
+                for line in output.lines() {
+                    writeln!(f, "    {}: {}", stream, line)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
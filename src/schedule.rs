@@ -1,12 +1,132 @@
 use crate::theme::Theme;
-use chrono::NaiveTime;
+use chrono::{Datelike, Days, NaiveDate, NaiveDateTime, NaiveTime, Weekday};
 use log;
 use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt;
 
+/// How many days back to look for the window still in effect before the first one of a day
+const LOOKBACK_DAYS: u64 = 366;
+/// How many days ahead to look for the next switch to the other theme
+const LOOKAHEAD_DAYS: u64 = 366;
+
+/// Why a schedule cannot be used
+#[derive(Debug, PartialEq)]
+pub enum ScheduleError {
+    /// `windows` is empty, so there is no theme to pick
+    Empty,
+    /// Two windows of equal precedence start at the same time on a common day with different
+    /// themes. Holds the positions of both windows in the config, counting from 1.
+    Conflict {
+        start: NaiveTime,
+        first: usize,
+        second: usize,
+    },
+    /// A window ends before it starts. Holds its position in the config, counting from 1.
+    ReversedDates {
+        window: usize,
+        from: NaiveDate,
+        to: NaiveDate,
+    },
+}
+
+impl fmt::Display for ScheduleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScheduleError::Empty => write!(f, "Schedule has no windows"),
+            ScheduleError::Conflict {
+                start,
+                first,
+                second,
+            } => write!(
+                f,
+                "Schedule windows {} and {} both start at {} on the same days with different themes",
+                first,
+                second,
+                start.format("%H:%M:%S")
+            ),
+            ScheduleError::ReversedDates { window, from, to } => write!(
+                f,
+                "Schedule window {} ends on {}, before it starts on {}",
+                window, to, from
+            ),
+        }
+    }
+}
+
+impl Error for ScheduleError {}
+
+/// A theme starting at `start` on the days the window applies to.
+///
+/// Without `weekdays`, `from` or `to` a window applies every day. On a given day only the most
+/// specific windows applying to it are used: windows limited by date beat windows limited by
+/// weekday, which beat everyday windows. So a `weekdays = ["Sat", "Sun"]` window replaces the
+/// everyday windows on weekends instead of mixing with them. Of windows with the same start
+/// and precedence the one listed last wins, though [`Schedule::validate`] rejects those that
+/// disagree on the theme.
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Window {
     pub theme: Theme,
     pub start: NaiveTime,
+    /// Days of the week the window applies to, e.g. `["Sat", "Sun"]`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub weekdays: Vec<Weekday>,
+    /// First day the window applies to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from: Option<NaiveDate>,
+    /// Last day the window applies to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to: Option<NaiveDate>,
+    /// Days the window does not apply to, e.g. holidays
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub except: Vec<NaiveDate>,
+}
+
+impl Window {
+    /// A window applying every day
+    pub fn daily(theme: Theme, start: NaiveTime) -> Self {
+        Window {
+            theme,
+            start,
+            weekdays: Vec::new(),
+            from: None,
+            to: None,
+            except: Vec::new(),
+        }
+    }
+
+    pub fn applies_on(&self, date: &NaiveDate) -> bool {
+        (self.weekdays.is_empty() || self.weekdays.contains(&date.weekday()))
+            && self.from.is_none_or(|from| from <= *date)
+            && self.to.is_none_or(|to| *date <= to)
+            && !self.except.contains(date)
+    }
+
+    /// Whether both windows apply on at least one common day, not counting `except`
+    fn overlaps(&self, other: &Window) -> bool {
+        let weekdays = self.weekdays.is_empty()
+            || other.weekdays.is_empty()
+            || self.weekdays.iter().any(|day| other.weekdays.contains(day));
+        let from = [self.from, other.from].into_iter().flatten().max();
+        let to = [self.to, other.to].into_iter().flatten().min();
+        let dates = match (from, to) {
+            (Some(from), Some(to)) => from <= to,
+            _ => true,
+        };
+        weekdays && dates
+    }
+
+    /// Precedence of the window over others applying on the same day
+    fn specificity(&self) -> u8 {
+        match (
+            self.from.is_some() || self.to.is_some(),
+            !self.weekdays.is_empty(),
+        ) {
+            (true, _) => 2,
+            (false, true) => 1,
+            (false, false) => 0,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -14,54 +134,176 @@ pub struct Schedule {
     pub windows: Vec<Window>,
 }
 
+impl fmt::Display for Window {
+    /// `17:00  dark  Sat, Sun from 2025-06-01, except 2025-06-09`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}\t{}\t",
+            self.start.format("%H:%M"),
+            self.theme.to_string()
+        )?;
+
+        let mut days = Vec::new();
+        if !self.weekdays.is_empty() {
+            let weekdays: Vec<String> = self.weekdays.iter().map(|day| day.to_string()).collect();
+            days.push(weekdays.join(", "));
+        }
+        match (self.from, self.to) {
+            (Some(from), Some(to)) => days.push(format!("{} to {}", from, to)),
+            (Some(from), None) => days.push(format!("from {}", from)),
+            (None, Some(to)) => days.push(format!("until {}", to)),
+            (None, None) => {}
+        }
+        if days.is_empty() {
+            days.push(String::from("every day"));
+        }
+        write!(f, "{}", days.join(" "))?;
+
+        if !self.except.is_empty() {
+            let except: Vec<String> = self.except.iter().map(|date| date.to_string()).collect();
+            write!(f, ", except {}", except.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
 impl Default for Schedule {
     fn default() -> Self {
         log::debug!("Creating default schedule");
         Schedule {
             windows: vec![
-                Window {
-                    theme: Theme::Light,
-                    start: NaiveTime::from_hms_opt(7, 0, 0)
+                Window::daily(
+                    Theme::Light,
+                    NaiveTime::from_hms_opt(7, 0, 0)
                         .expect("Failure to generate default start time"),
-                },
-                Window {
-                    theme: Theme::Dark,
-                    start: NaiveTime::from_hms_opt(17, 0, 0)
+                ),
+                Window::daily(
+                    Theme::Dark,
+                    NaiveTime::from_hms_opt(17, 0, 0)
                         .expect("Failure to generate default start time"),
-                },
+                ),
             ],
         }
     }
 }
 
 impl Schedule {
-    pub fn theme_from_time(&self, time: &NaiveTime) -> Theme {
+    /// Check that the schedule has a window, that no window ends before it starts and that no
+    /// two windows contend for the same moment.
+    pub fn validate(&self) -> Result<(), ScheduleError> {
+        if self.windows.is_empty() {
+            return Err(ScheduleError::Empty);
+        }
+
+        for (i, window) in self.windows.iter().enumerate() {
+            if let (Some(from), Some(to)) = (window.from, window.to)
+                && from > to
+            {
+                return Err(ScheduleError::ReversedDates {
+                    window: i + 1,
+                    from,
+                    to,
+                });
+            }
+        }
+
+        for (i, first) in self.windows.iter().enumerate() {
+            for (j, second) in self.windows.iter().enumerate().skip(i + 1) {
+                if first.start == second.start
+                    && first.theme != second.theme
+                    && first.specificity() == second.specificity()
+                    && first.overlaps(second)
+                {
+                    return Err(ScheduleError::Conflict {
+                        start: first.start,
+                        first: i + 1,
+                        second: j + 1,
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Windows in effect on `date`, ordered by start time: the most specific of those applying
+    /// to it. Windows with the same start keep their config order.
+    pub fn windows_on(&self, date: &NaiveDate) -> Vec<&Window> {
+        let applying: Vec<&Window> = self.windows.iter().filter(|w| w.applies_on(date)).collect();
+        let Some(specificity) = applying.iter().map(|w| w.specificity()).max() else {
+            return Vec::new();
+        };
+
+        let mut windows: Vec<&Window> = applying
+            .into_iter()
+            .filter(|w| w.specificity() == specificity)
+            .collect();
+        windows.sort_by_key(|w| w.start);
+        windows
+    }
+
+    /// All windows by start time, in config order for equal starts
+    pub fn ordered(&self) -> Vec<&Window> {
+        let mut windows: Vec<&Window> = self.windows.iter().collect();
+        windows.sort_by_key(|w| w.start);
+        windows
+    }
+
+    /// The first time after `datetime` at which the schedule switches to the other theme, and
+    /// that theme. `None` if it does not switch within a year.
+    pub fn next_transition(&self, datetime: &NaiveDateTime) -> Option<(NaiveDateTime, Theme)> {
+        let current = self.theme_from_time(datetime).ok()?;
+        let date = datetime.date();
+        (0..=LOOKAHEAD_DAYS)
+            .filter_map(|days| date.checked_add_days(Days::new(days)))
+            .flat_map(|day| {
+                self.windows_on(&day)
+                    .into_iter()
+                    .map(move |w| (day.and_time(w.start), w))
+            })
+            .find(|(start, w)| start > datetime && w.theme != current)
+            .map(|(start, w)| (start, w.theme.clone()))
+    }
+
+    /// The theme in effect at `datetime`. Fails only for a schedule without windows.
+    pub fn theme_from_time(&self, datetime: &NaiveDateTime) -> Result<Theme, ScheduleError> {
         log::debug!(
             "Determining theme from schedule for time: {}",
-            time.format("%H:%M:%S")
+            datetime.format("%Y-%m-%d %H:%M:%S")
         );
 
-        // Sort windows by start time (if not guaranteed)
-        let mut sorted = self.windows.clone();
-        sorted.sort_by_key(|w| w.start);
-
-        // Find the last window that starts before or at current time
-        let theme = sorted
+        // Find the last window of the day that starts before or at the current time
+        let date = datetime.date();
+        let theme = self
+            .windows_on(&date)
             .iter()
             .rev()
-            .find(|w| w.start <= *time)
-            .map(|w| w.theme.clone());
+            .find(|w| w.start <= datetime.time())
+            .map(|w| w.theme.clone())
+            // Before the first window of the day, the last window of an earlier day still holds
+            .or_else(|| {
+                (1..=LOOKBACK_DAYS)
+                    .filter_map(|days| date.checked_sub_days(Days::new(days)))
+                    .find_map(|day| self.windows_on(&day).last().map(|w| w.theme.clone()))
+            });
 
-        // If none matched, use the last window (wrap around midnight)
-        let result = theme.unwrap_or_else(|| sorted.last().unwrap().theme.clone());
+        // If no window applied within a year, use the latest-starting one, the last listed on ties
+        let result = match theme {
+            Some(theme) => theme,
+            None => self
+                .ordered()
+                .last()
+                .map(|w| w.theme.clone())
+                .ok_or(ScheduleError::Empty)?,
+        };
 
         log::debug!(
             "Schedule lookup at {}: theme = {}",
-            time.format("%H:%M:%S"),
+            datetime.format("%Y-%m-%d %H:%M:%S"),
             result.to_string()
         );
 
-        result
+        Ok(result)
     }
 }
 
@@ -70,13 +312,241 @@ mod tests {
     use super::*;
     use rstest::rstest;
 
+    fn at(datetime: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(datetime, "%Y-%m-%d %H:%M").unwrap()
+    }
+
     #[rstest]
     #[case(NaiveTime::from_hms_opt(0, 0, 0).unwrap(), Theme::Dark)]
     #[case(NaiveTime::from_hms_opt(10, 0, 0).unwrap(), Theme::Light)]
     #[case(NaiveTime::from_hms_opt(21, 0, 0).unwrap(), Theme::Dark)]
     fn test_theme_from_time(#[case] time: NaiveTime, #[case] theme: Theme) {
         let schedule = Schedule::default();
-        let result = schedule.theme_from_time(&time);
+        let date = NaiveDate::from_ymd_opt(2025, 6, 4).unwrap();
+        let result = schedule.theme_from_time(&date.and_time(time)).unwrap();
         assert_eq!(result, theme);
     }
+
+    /// Light 07:00 and dark 17:00 every day, dark all weekend, light from 09:00 on a
+    /// Christmas break, and no light on 2025-06-09 (a Monday off)
+    fn rules() -> Schedule {
+        toml::from_str(
+            r#"
+            [[windows]]
+            theme = "Light"
+            start = "07:00:00"
+            except = ["2025-06-09"]
+
+            [[windows]]
+            theme = "Dark"
+            start = "17:00:00"
+
+            [[windows]]
+            theme = "Dark"
+            start = "00:00:00"
+            weekdays = ["Sat", "Sun"]
+
+            [[windows]]
+            theme = "Light"
+            start = "09:00:00"
+            from = "2025-12-24"
+            to = "2025-12-26"
+
+            [[windows]]
+            theme = "Dark"
+            start = "16:00:00"
+            from = "2025-12-24"
+            to = "2025-12-26"
+            "#,
+        )
+        .unwrap()
+    }
+
+    #[rstest]
+    #[case::weekday_morning("2025-06-04 10:00", Theme::Light)]
+    #[case::weekday_evening("2025-06-04 18:00", Theme::Dark)]
+    #[case::weekday_before_first_window("2025-06-04 06:00", Theme::Dark)]
+    #[case::saturday_noon("2025-06-07 12:00", Theme::Dark)]
+    #[case::sunday_noon("2025-06-08 12:00", Theme::Dark)]
+    #[case::monday_after_weekend("2025-06-09 03:00", Theme::Dark)]
+    #[case::exception("2025-06-09 10:00", Theme::Dark)]
+    #[case::after_exception("2025-06-10 10:00", Theme::Light)]
+    #[case::date_range_before_first_window("2025-12-24 08:00", Theme::Dark)]
+    #[case::date_range_morning("2025-12-25 10:00", Theme::Light)]
+    #[case::date_range_afternoon("2025-12-25 16:30", Theme::Dark)]
+    #[case::weekend_after_date_range("2025-12-27 12:00", Theme::Dark)]
+    #[case::after_date_range("2025-12-29 10:00", Theme::Light)]
+    fn test_theme_from_time_rules(#[case] datetime: &str, #[case] theme: Theme) {
+        assert_eq!(rules().theme_from_time(&at(datetime)).unwrap(), theme);
+    }
+
+    #[rstest]
+    #[case::evening("2025-06-04 18:00", Some(("2025-06-05 07:00", Theme::Light)))]
+    #[case::friday_morning("2025-06-06 10:00", Some(("2025-06-06 17:00", Theme::Dark)))]
+    #[case::friday_evening("2025-06-06 18:00", Some(("2025-06-10 07:00", Theme::Light)))]
+    #[case::at_window_start("2025-06-04 07:00", Some(("2025-06-04 17:00", Theme::Dark)))]
+    #[case::date_range("2025-12-24 08:00", Some(("2025-12-24 09:00", Theme::Light)))]
+    fn test_next_transition(#[case] datetime: &str, #[case] expected: Option<(&str, Theme)>) {
+        let expected = expected.map(|(datetime, theme)| (at(datetime), theme));
+        assert_eq!(rules().next_transition(&at(datetime)), expected);
+    }
+
+    #[test]
+    fn test_window_display() {
+        let schedule = rules();
+        let lines: Vec<String> = schedule.ordered().iter().map(|w| w.to_string()).collect();
+        assert_eq!(
+            lines,
+            [
+                "00:00\tdark\tSat, Sun",
+                "07:00\tlight\tevery day, except 2025-06-09",
+                "09:00\tlight\t2025-12-24 to 2025-12-26",
+                "16:00\tdark\t2025-12-24 to 2025-12-26",
+                "17:00\tdark\tevery day",
+            ]
+        );
+    }
+
+    #[rstest]
+    #[case::weekday("2025-06-04", &["07:00:00", "17:00:00"])]
+    #[case::weekend("2025-06-07", &["00:00:00"])]
+    #[case::exception("2025-06-09", &["17:00:00"])]
+    #[case::date_range_on_weekday("2025-12-24", &["09:00:00", "16:00:00"])]
+    fn test_windows_on(#[case] date: &str, #[case] starts: &[&str]) {
+        let schedule = rules();
+        let date = NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap();
+        let found: Vec<String> = schedule
+            .windows_on(&date)
+            .iter()
+            .map(|w| w.start.format("%H:%M:%S").to_string())
+            .collect();
+        assert_eq!(found, starts);
+    }
+
+    fn window(theme: Theme, start: &str, rule: &str) -> Window {
+        let mut window: Window = toml::from_str(&format!(
+            "theme = \"Light\"\nstart = \"{}\"\n{}",
+            start, rule
+        ))
+        .unwrap();
+        window.theme = theme;
+        window
+    }
+
+    #[test]
+    fn test_rules_are_valid() {
+        assert_eq!(rules().validate(), Ok(()));
+        assert_eq!(Schedule::default().validate(), Ok(()));
+    }
+
+    #[rstest]
+    #[case::same_theme(vec![
+        window(Theme::Dark, "17:00:00", ""),
+        window(Theme::Dark, "17:00:00", ""),
+    ], None)]
+    #[case::everyday(vec![
+        window(Theme::Light, "07:00:00", ""),
+        window(Theme::Dark, "17:00:00", ""),
+        window(Theme::Light, "17:00:00", ""),
+    ], Some((2, 3)))]
+    #[case::different_precedence(vec![
+        window(Theme::Dark, "07:00:00", ""),
+        window(Theme::Light, "07:00:00", "weekdays = [\"Sat\"]"),
+    ], None)]
+    #[case::common_weekday(vec![
+        window(Theme::Dark, "07:00:00", "weekdays = [\"Fri\", \"Sat\"]"),
+        window(Theme::Light, "07:00:00", "weekdays = [\"Sat\", \"Sun\"]"),
+    ], Some((1, 2)))]
+    #[case::distinct_weekdays(vec![
+        window(Theme::Dark, "07:00:00", "weekdays = [\"Sat\"]"),
+        window(Theme::Light, "07:00:00", "weekdays = [\"Sun\"]"),
+    ], None)]
+    #[case::overlapping_dates(vec![
+        window(Theme::Dark, "07:00:00", "from = \"2025-12-20\"\nto = \"2025-12-26\""),
+        window(Theme::Light, "07:00:00", "from = \"2025-12-26\""),
+    ], Some((1, 2)))]
+    #[case::separate_dates(vec![
+        window(Theme::Dark, "07:00:00", "from = \"2025-12-20\"\nto = \"2025-12-25\""),
+        window(Theme::Light, "07:00:00", "from = \"2025-12-26\""),
+    ], None)]
+    fn test_validate(#[case] windows: Vec<Window>, #[case] conflict: Option<(usize, usize)>) {
+        let schedule = Schedule { windows };
+        let expected = match conflict {
+            Some((first, second)) => Err(ScheduleError::Conflict {
+                start: schedule.windows[first - 1].start,
+                first,
+                second,
+            }),
+            None => Ok(()),
+        };
+        assert_eq!(schedule.validate(), expected);
+    }
+
+    #[test]
+    fn test_validate_reversed_dates() {
+        let schedule = Schedule {
+            windows: vec![
+                window(Theme::Light, "07:00:00", ""),
+                window(
+                    Theme::Dark,
+                    "07:00:00",
+                    "from = \"2025-12-26\"\nto = \"2025-12-20\"",
+                ),
+            ],
+        };
+        assert_eq!(
+            schedule.validate(),
+            Err(ScheduleError::ReversedDates {
+                window: 2,
+                from: NaiveDate::from_ymd_opt(2025, 12, 26).unwrap(),
+                to: NaiveDate::from_ymd_opt(2025, 12, 20).unwrap(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_empty_schedule() {
+        let schedule = Schedule {
+            windows: Vec::new(),
+        };
+        assert_eq!(schedule.validate(), Err(ScheduleError::Empty));
+        assert_eq!(
+            schedule.theme_from_time(&at("2025-06-04 10:00")),
+            Err(ScheduleError::Empty)
+        );
+        assert_eq!(schedule.next_transition(&at("2025-06-04 10:00")), None);
+    }
+
+    /// Windows sharing a start and precedence are rejected by `validate`, but evaluating them
+    /// anyway picks the one listed last, before and after the start.
+    #[rstest]
+    #[case::at_start("2025-06-04 17:00", Theme::Light)]
+    #[case::before_start("2025-06-04 10:00", Theme::Light)]
+    fn test_tie_breaking(#[case] datetime: &str, #[case] theme: Theme) {
+        let schedule = Schedule {
+            windows: vec![
+                window(Theme::Dark, "17:00:00", ""),
+                window(Theme::Light, "17:00:00", ""),
+            ],
+        };
+        assert!(schedule.validate().is_err());
+        assert_eq!(schedule.theme_from_time(&at(datetime)).unwrap(), theme);
+    }
+
+    /// Only dates far from any window: the latest-starting window applies, the last listed on
+    /// ties.
+    #[test]
+    fn test_no_window_within_a_year() {
+        let schedule = Schedule {
+            windows: vec![
+                window(Theme::Dark, "20:00:00", "from = \"2030-01-01\""),
+                window(Theme::Light, "20:00:00", "from = \"2031-01-01\""),
+                window(Theme::Dark, "08:00:00", "from = \"2030-01-01\""),
+            ],
+        };
+        assert_eq!(
+            schedule.theme_from_time(&at("2025-06-04 10:00")).unwrap(),
+            Theme::Light
+        );
+    }
 }
@@ -0,0 +1,578 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::io::ErrorKind;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Instant;
+
+use crate::command;
+use crate::config::Config;
+use crate::report::{Outcome, ScriptReport, Trigger};
+use crate::theme::Theme;
+
+/// When a script runs relative to the rest of the switch
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Hook {
+    Pre,
+    Post,
+}
+
+impl fmt::Display for Hook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Hook::Pre => write!(f, "pre"),
+            Hook::Post => write!(f, "post"),
+        }
+    }
+}
+
+fn default_max_parallel() -> usize {
+    4
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ScriptOptions {
+    /// Do not switch at all when a pre-hook fails or times out
+    #[serde(default)]
+    pub abort_on_pre_failure: bool,
+    /// File names of scripts that may run alongside other parallel scripts, in addition to
+    /// those named `*.parallel` or `*.parallel.<ext>`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub parallel: Vec<String>,
+    /// How many parallel scripts run at once
+    #[serde(default = "default_max_parallel")]
+    pub max_parallel: usize,
+}
+
+impl Default for ScriptOptions {
+    fn default() -> Self {
+        ScriptOptions {
+            abort_on_pre_failure: false,
+            parallel: Vec::new(),
+            max_parallel: default_max_parallel(),
+        }
+    }
+}
+
+impl ScriptOptions {
+    fn is_parallel(&self, script: &Path) -> bool {
+        let name = script
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        name.ends_with(".parallel") || name.contains(".parallel.") || self.parallel.contains(&name)
+    }
+}
+
+/// What a script learns about the switch it runs for
+pub struct Environment<'a> {
+    pub theme: &'a Theme,
+    pub previous: Option<&'a Theme>,
+    pub trigger: &'a Trigger,
+    pub hook: Hook,
+    /// End of the phase the scripts run in; scripts still running then are killed
+    pub deadline: Option<Instant>,
+}
+
+/// Directories holding the scripts of a hook, in precedence order.
+///
+/// Files directly in `any/` and `<theme>/` are post-hooks, as they were before hooks had
+/// their own `pre/` and `post/` directories.
+fn hook_dirs(config: &Config, theme: &Theme, hook: Hook) -> Vec<PathBuf> {
+    let theme_dir = match theme {
+        Theme::Dark => &config.dark_scripts_dir,
+        Theme::Light => &config.light_scripts_dir,
+    };
+    match hook {
+        Hook::Pre => vec![config.any_scripts_dir.join("pre"), theme_dir.join("pre")],
+        Hook::Post => vec![
+            config.any_scripts_dir.clone(),
+            config.any_scripts_dir.join("post"),
+            theme_dir.clone(),
+            theme_dir.join("post"),
+        ],
+    }
+}
+
+/// All scripts in the given directories, ordered by file name so `10-foo` runs before
+/// `20-bar` wherever they live. Equal names keep the directory order.
+fn collect(dirs: &[PathBuf]) -> Vec<PathBuf> {
+    let mut scripts: Vec<(usize, PathBuf)> = Vec::new();
+    for (index, dir) in dirs.iter().enumerate() {
+        if !dir.is_dir() {
+            log::debug!(
+                "Scripts directory does not exist: {}",
+                dir.to_string_lossy()
+            );
+            continue;
+        }
+        match fs::read_dir(dir) {
+            Ok(entries) => scripts.extend(
+                entries
+                    .filter_map(|entry| entry.ok().map(|e| e.path()))
+                    .filter(|path| path.is_file())
+                    .map(|path| (index, path)),
+            ),
+            Err(e) => log::error!(
+                "Failed to read scripts directory {}: {}",
+                dir.to_string_lossy(),
+                e
+            ),
+        }
+    }
+    scripts.sort_by(|(a_index, a), (b_index, b)| {
+        (a.file_name(), a_index).cmp(&(b.file_name(), b_index))
+    });
+    scripts.into_iter().map(|(_, path)| path).collect()
+}
+
+fn is_executable(path: &Path) -> bool {
+    fs::metadata(path)
+        .map(|metadata| metadata.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+/// Executable scripts run directly, so their shebang picks the interpreter. Anything else is
+/// passed to `sh` as an argument, never interpolated into a shell command.
+fn command_for(path: &Path) -> Command {
+    match is_executable(path) {
+        true => Command::new(path),
+        false => {
+            let mut command = Command::new("sh");
+            command.arg(path);
+            command
+        }
+    }
+}
+
+/// Log a script's output line by line, prefixed with the script name
+fn log_output(path: &Path, stream: &str, output: &str, failed: bool) {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    for line in output.lines() {
+        match failed {
+            true => log::warn!("{} {}: {}", name, stream, line),
+            false => log::info!("{} {}: {}", name, stream, line),
+        }
+    }
+}
+
+/// Run a single script and capture its output.
+fn run_script(path: &Path, environment: &Environment, config: &Config) -> ScriptReport {
+    log::info!("Running script: {}", path.to_string_lossy());
+    let started = Instant::now();
+    let timeout = command::within(config.timeouts.for_script(path), environment.deadline);
+    let mut command = command_for(path);
+    command
+        .env("KSWITCH_THEME", environment.theme.to_string())
+        .env(
+            "KSWITCH_PREVIOUS_THEME",
+            environment
+                .previous
+                .map(|theme| theme.to_string())
+                .unwrap_or_default(),
+        )
+        .env("KSWITCH_TRIGGER", environment.trigger.to_string())
+        .env("KSWITCH_HOOK", environment.hook.to_string());
+
+    let (outcome, stdout, stderr) = match command::output(&mut command, timeout) {
+        Ok(output) => {
+            let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+            let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+            let outcome = if output.status.success() {
+                log::info!("Script executed successfully: {}", path.to_string_lossy());
+                Outcome::Applied
+            } else {
+                log::warn!(
+                    "Script exited with non-zero status: {}: {}",
+                    path.to_string_lossy(),
+                    output.status
+                );
+                Outcome::Failed(output.status.to_string())
+            };
+            log_output(path, "stdout", &stdout, outcome.is_problem());
+            log_output(path, "stderr", &stderr, outcome.is_problem());
+            (outcome, stdout, stderr)
+        }
+        Err(e) if e.kind() == ErrorKind::TimedOut => {
+            log::error!("Script timed out: {}", path.to_string_lossy());
+            // Whatever it wrote before it was killed tells where it got stuck
+            let (stdout, stderr) = match command::partial_output(&e) {
+                Some(partial) => (
+                    String::from_utf8_lossy(&partial.stdout).to_string(),
+                    String::from_utf8_lossy(&partial.stderr).to_string(),
+                ),
+                None => (String::new(), String::new()),
+            };
+            log_output(path, "stdout", &stdout, true);
+            log_output(path, "stderr", &stderr, true);
+            (Outcome::TimedOut, stdout, stderr)
+        }
+        Err(e) => {
+            log::error!("Failed to execute script {}: {}", path.to_string_lossy(), e);
+            (Outcome::Failed(e.to_string()), String::new(), String::new())
+        }
+    };
+
+    ScriptReport {
+        path: path.to_path_buf(),
+        hook: environment.hook,
+        outcome,
+        duration: started.elapsed(),
+        stdout,
+        stderr,
+    }
+}
+
+/// Split scripts into batches that run one after another. Consecutive parallel scripts share
+/// a batch, every other script gets a batch of its own.
+fn batches<'a>(scripts: &'a [PathBuf], options: &ScriptOptions) -> Vec<Vec<&'a PathBuf>> {
+    let mut batches: Vec<Vec<&PathBuf>> = Vec::new();
+    let mut previous_parallel = false;
+    for script in scripts {
+        let parallel = options.is_parallel(script);
+        match batches.last_mut() {
+            Some(batch) if parallel && previous_parallel => batch.push(script),
+            _ => batches.push(vec![script]),
+        }
+        previous_parallel = parallel;
+    }
+    batches
+}
+
+/// Run a batch of scripts with at most `max_parallel` at a time. Reports keep the batch order.
+fn run_batch(batch: &[&PathBuf], environment: &Environment, config: &Config) -> Vec<ScriptReport> {
+    if batch.len() == 1 {
+        return vec![run_script(batch[0], environment, config)];
+    }
+
+    let workers = config.scripts.max_parallel.clamp(1, batch.len());
+    log::debug!(
+        "Running {} parallel scripts with {} workers",
+        batch.len(),
+        workers
+    );
+    let next = AtomicUsize::new(0);
+    let reports: Mutex<Vec<Option<ScriptReport>>> = Mutex::new(vec![None; batch.len()]);
+
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| {
+                loop {
+                    let index = next.fetch_add(1, Ordering::SeqCst);
+                    let Some(path) = batch.get(index) else {
+                        break;
+                    };
+                    let report = run_script(path, environment, config);
+                    if let Ok(mut reports) = reports.lock() {
+                        reports[index] = Some(report);
+                    }
+                }
+            });
+        }
+    });
+
+    reports
+        .into_inner()
+        .unwrap_or_default()
+        .into_iter()
+        .flatten()
+        .collect()
+}
+
+/// Sum up the scripts of a hook as a single component outcome.
+pub fn outcome(reports: &[ScriptReport]) -> Outcome {
+    let failures: Vec<String> = reports
+        .iter()
+        .filter(|report| report.outcome.is_problem())
+        .map(|report| {
+            format!(
+                "{} {}",
+                report
+                    .path
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy(),
+                report.outcome
+            )
+        })
+        .collect();
+    match failures.is_empty() {
+        true => Outcome::Applied,
+        false => Outcome::Failed(failures.join("; ")),
+    }
+}
+
+/// Run the scripts of a hook in file name order, running consecutive parallel scripts
+/// concurrently.
+pub fn run(environment: &Environment, config: &Config) -> Vec<ScriptReport> {
+    let dirs = hook_dirs(config, environment.theme, environment.hook);
+    log::debug!(
+        "Looking for {} theme {}-hooks in {:?}",
+        environment.theme.to_string(),
+        environment.hook,
+        dirs
+    );
+
+    let scripts = collect(&dirs);
+    if scripts.is_empty() {
+        log::debug!("No {}-hooks found", environment.hook);
+        return Vec::new();
+    }
+
+    log::info!(
+        "Found {} theme {}-hook(s) to execute",
+        scripts.len(),
+        environment.hook
+    );
+
+    batches(&scripts, &config.scripts)
+        .into_iter()
+        .flat_map(|batch| run_batch(&batch, environment, config))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env::temp_dir;
+
+    fn write_script(path: &Path, contents: &str, executable: bool) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+        let mode = if executable { 0o755 } else { 0o644 };
+        fs::set_permissions(path, fs::Permissions::from_mode(mode)).unwrap();
+    }
+
+    fn test_config(root: &Path) -> Config {
+        Config {
+            light_scripts_dir: root.join("light"),
+            dark_scripts_dir: root.join("dark"),
+            any_scripts_dir: root.join("any"),
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn test_collect_orders_by_file_name() {
+        let root = temp_dir().join("kswitch_test_scripts_order");
+        let _ = fs::remove_dir_all(&root);
+        let config = test_config(&root);
+        for path in [
+            "dark/20-tmux",
+            "any/10-vim",
+            "dark/post/30-bat",
+            "any/post/20-tmux",
+            "dark/pre/10-check",
+            "light/10-vim",
+        ] {
+            write_script(&root.join(path), "", true);
+        }
+
+        assert_eq!(
+            collect(&hook_dirs(&config, &Theme::Dark, Hook::Post)),
+            vec![
+                root.join("any/10-vim"),
+                root.join("any/post/20-tmux"),
+                root.join("dark/20-tmux"),
+                root.join("dark/post/30-bat"),
+            ]
+        );
+        assert_eq!(
+            collect(&hook_dirs(&config, &Theme::Dark, Hook::Pre)),
+            vec![root.join("dark/pre/10-check")]
+        );
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_run_sets_environment() {
+        let root = temp_dir().join("kswitch_test_scripts_environment");
+        let _ = fs::remove_dir_all(&root);
+        let config = test_config(&root);
+        let out = root.join("out");
+        write_script(
+            &root.join("dark/pre/10-env"),
+            &format!(
+                "#!/bin/sh\necho \"$KSWITCH_THEME $KSWITCH_PREVIOUS_THEME $KSWITCH_TRIGGER $KSWITCH_HOOK\" > '{}'\n",
+                out.display()
+            ),
+            true,
+        );
+
+        let reports = run(
+            &Environment {
+                theme: &Theme::Dark,
+                previous: Some(&Theme::Light),
+                trigger: &Trigger::Toggle,
+                hook: Hook::Pre,
+                deadline: None,
+            },
+            &config,
+        );
+
+        assert_eq!(outcome(&reports), Outcome::Applied);
+        assert_eq!(fs::read_to_string(&out).unwrap(), "dark light toggle pre\n");
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_run_captures_output() {
+        // Spaces and shell metacharacters must reach the script untouched
+        let root = temp_dir().join("kswitch test scripts; $(touch pwned)");
+        let _ = fs::remove_dir_all(&root);
+        let config = test_config(&root);
+        write_script(
+            &root.join("light/10 it's fine.sh"),
+            "echo \"hello from $KSWITCH_THEME\"\n",
+            false,
+        );
+        write_script(
+            &root.join("light/20-fail"),
+            "#!/bin/sh\necho broken >&2\nexit 3\n",
+            true,
+        );
+
+        let reports = run(
+            &Environment {
+                theme: &Theme::Light,
+                previous: None,
+                trigger: &Trigger::Set,
+                hook: Hook::Post,
+                deadline: None,
+            },
+            &config,
+        );
+
+        assert_eq!(reports.len(), 2);
+        assert_eq!(reports[0].outcome, Outcome::Applied);
+        assert_eq!(reports[0].stdout, "hello from light\n");
+        assert_eq!(
+            reports[1].outcome,
+            Outcome::Failed(String::from("exit status: 3"))
+        );
+        assert_eq!(reports[1].stderr, "broken\n");
+        assert_eq!(
+            outcome(&reports),
+            Outcome::Failed(String::from("20-fail failed: exit status: 3"))
+        );
+        assert!(!root.join("light/pwned").exists());
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_run_keeps_output_of_timed_out_script() {
+        let root = temp_dir().join("kswitch_test_scripts_timeout");
+        let _ = fs::remove_dir_all(&root);
+        let config = test_config(&root);
+        write_script(
+            &root.join("dark/10-stuck"),
+            "#!/bin/sh\necho half way\necho waiting for lock >&2\nsleep 10\n",
+            true,
+        );
+
+        let reports = run(
+            &Environment {
+                theme: &Theme::Dark,
+                previous: None,
+                trigger: &Trigger::Set,
+                hook: Hook::Post,
+                deadline: Some(Instant::now() + std::time::Duration::from_millis(300)),
+            },
+            &config,
+        );
+
+        assert_eq!(reports[0].outcome, Outcome::TimedOut);
+        assert_eq!(reports[0].stdout, "half way\n");
+        assert_eq!(reports[0].stderr, "waiting for lock\n");
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_batches() {
+        let options = ScriptOptions {
+            parallel: vec![String::from("30-bat.sh")],
+            ..ScriptOptions::default()
+        };
+        let scripts: Vec<PathBuf> = [
+            "10-check.sh",
+            "20-vim.parallel.sh",
+            "25-tmux.parallel",
+            "30-bat.sh",
+            "40-reload.sh",
+            "50-code.parallel.sh",
+        ]
+        .iter()
+        .map(|name| PathBuf::from("/scripts").join(name))
+        .collect();
+
+        let names: Vec<Vec<String>> = batches(&scripts, &options)
+            .iter()
+            .map(|batch| {
+                batch
+                    .iter()
+                    .map(|path| path.file_name().unwrap().to_string_lossy().to_string())
+                    .collect()
+            })
+            .collect();
+
+        assert_eq!(
+            names,
+            vec![
+                vec!["10-check.sh"],
+                vec!["20-vim.parallel.sh", "25-tmux.parallel", "30-bat.sh"],
+                vec!["40-reload.sh"],
+                vec!["50-code.parallel.sh"],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_run_parallel_scripts() {
+        let root = temp_dir().join("kswitch_test_scripts_parallel");
+        let _ = fs::remove_dir_all(&root);
+        let mut config = test_config(&root);
+        config.scripts.max_parallel = 3;
+        for name in ["10-a.parallel", "20-b.parallel", "30-c.parallel"] {
+            write_script(
+                &root.join("dark").join(name),
+                "#!/bin/sh\nsleep 0.5\necho $0\n",
+                true,
+            );
+        }
+
+        let start = Instant::now();
+        let reports = run(
+            &Environment {
+                theme: &Theme::Dark,
+                previous: None,
+                trigger: &Trigger::Set,
+                hook: Hook::Post,
+                deadline: None,
+            },
+            &config,
+        );
+
+        assert!(start.elapsed().as_millis() < 1400, "{:?}", start.elapsed());
+        let paths: Vec<PathBuf> = reports.iter().map(|r| r.path.clone()).collect();
+        assert_eq!(
+            paths,
+            vec![
+                root.join("dark/10-a.parallel"),
+                root.join("dark/20-b.parallel"),
+                root.join("dark/30-c.parallel"),
+            ]
+        );
+        assert_eq!(outcome(&reports), Outcome::Applied);
+
+        let _ = fs::remove_dir_all(&root);
+    }
+}
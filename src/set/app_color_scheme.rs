@@ -0,0 +1,114 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+use crate::kconfig;
+use crate::theme::AppColorScheme;
+
+fn config_dir() -> PathBuf {
+    dirs::config_dir().unwrap_or(PathBuf::from("~/.config"))
+}
+
+/// Write the overrides of one application into its `<app>rc`.
+///
+/// The window color scheme lives in `[UiSettings] ColorScheme`, the KTextEditor color theme in
+/// `[KTextEditor Renderer] Color Theme`.
+fn write_app(config_dir: &Path, app: &str, scheme: &AppColorScheme) -> Result<(), Box<dyn Error>> {
+    if app.is_empty() || app.contains('/') {
+        return Err(format!("Invalid application name: {}", app).into());
+    }
+    let path = config_dir.join(format!("{}rc", app));
+
+    let (color_scheme, editor_theme) = match scheme {
+        AppColorScheme::Name(name) => (Some(name), None),
+        AppColorScheme::Detailed {
+            color_scheme,
+            editor_theme,
+        } => (color_scheme.as_ref(), editor_theme.as_ref()),
+    };
+    if let Some(color_scheme) = color_scheme {
+        kconfig::write_entry(&path, &["UiSettings"], "ColorScheme", color_scheme)?;
+    }
+    if let Some(editor_theme) = editor_theme {
+        kconfig::write_entry(
+            &path,
+            &["KTextEditor Renderer"],
+            "Color Theme",
+            editor_theme,
+        )?;
+    }
+    Ok(())
+}
+
+/// Applications read these settings on start, so running instances keep their colors until
+/// they are restarted.
+pub fn set(schemes: &BTreeMap<String, AppColorScheme>) -> Result<(), Box<dyn Error>> {
+    log::info!("Applying application color schemes: {:?}", schemes);
+
+    let config_dir = config_dir();
+    let mut failures = Vec::new();
+    for (app, scheme) in schemes.iter() {
+        match write_app(&config_dir, app, scheme) {
+            Ok(_) => log::debug!("Color scheme of {} set to {:?}", app, scheme),
+            Err(e) => {
+                log::error!("Failed to set color scheme of {}: {}", app, e);
+                failures.push(format!("{}: {}", app, e));
+            }
+        }
+    }
+
+    match failures.is_empty() {
+        true => {
+            log::info!("Application color schemes applied successfully");
+            Ok(())
+        }
+        false => Err(failures.join("; ").into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env::temp_dir;
+    use std::fs;
+
+    #[test]
+    fn test_write_app() {
+        let dir = temp_dir().join("kswitch_test_app_color_schemes");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("katerc"),
+            "[KTextEditor Renderer]\nColor Theme=Breeze Light\nShow Indentation Lines=true\n",
+        )
+        .unwrap();
+
+        let schemes: BTreeMap<String, AppColorScheme> = toml::from_str(
+            "okular = \"BreezeDark\"\nkate = { color_scheme = \"BreezeDark\", editor_theme = \"Breeze Dark\" }",
+        )
+        .unwrap();
+        for (app, scheme) in schemes.iter() {
+            write_app(&dir, app, scheme).unwrap();
+        }
+
+        assert_eq!(
+            fs::read_to_string(dir.join("okularrc")).unwrap(),
+            "[UiSettings]\nColorScheme=BreezeDark\n"
+        );
+        assert_eq!(
+            kconfig::read_entry(
+                &dir.join("katerc"),
+                &["KTextEditor Renderer"],
+                "Color Theme"
+            ),
+            Some(String::from("Breeze Dark"))
+        );
+        assert_eq!(
+            kconfig::read_entry(&dir.join("katerc"), &["UiSettings"], "ColorScheme"),
+            Some(String::from("BreezeDark"))
+        );
+        assert!(write_app(&dir, "../evil", &schemes["okular"]).is_err());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,103 @@
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+fn config_path() -> PathBuf {
+    match env::var_os("BAT_CONFIG_PATH") {
+        Some(path) => PathBuf::from(path),
+        None => dirs::config_dir()
+            .unwrap_or(PathBuf::from("~/.config"))
+            .join("bat/config"),
+    }
+}
+
+/// Set `--<option>=<value>` in a file holding one command line option per line, as read by bat
+/// and fzf. Replaces an existing line for the option or appends one.
+pub fn write_option(path: &Path, option: &str, value: &str) -> Result<(), Box<dyn Error>> {
+    let flag = format!("--{}", option);
+    let line = format!("{}=\"{}\"", flag, value.replace('"', "\\\""));
+
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(Box::new(e)),
+    };
+
+    let mut found = false;
+    let mut lines: Vec<String> = Vec::new();
+    for existing in contents.lines() {
+        let trimmed = existing.trim_start();
+        let is_option = trimmed
+            .strip_prefix(&flag)
+            .is_some_and(|rest| rest.starts_with('=') || rest.starts_with(' '));
+        if is_option && !found {
+            lines.push(line.clone());
+            found = true;
+        } else if !is_option {
+            lines.push(existing.to_string());
+        }
+    }
+    if !found {
+        lines.push(line);
+    }
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, lines.join("\n") + "\n")?;
+    Ok(())
+}
+
+pub fn set(theme: &str) -> Result<(), Box<dyn Error>> {
+    log::info!("Applying bat theme: {}", theme);
+
+    // bat reads its config on every run, so there is nothing to notify
+    let path = config_path();
+    match write_option(&path, "theme", theme) {
+        Ok(_) => {
+            log::info!("bat theme applied successfully: {}", theme);
+            Ok(())
+        }
+        Err(e) => {
+            log::error!(
+                "Failed to write bat config {}: {}",
+                path.to_string_lossy(),
+                e
+            );
+            Err(e)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env::temp_dir;
+
+    #[test]
+    fn test_write_option() {
+        let path = temp_dir().join("kswitch_test_bat_config");
+        fs::write(
+            &path,
+            "# Set the theme\n--theme=\"GitHub\"\n--theme-light=\"GitHub\"\n--italic-text=always\n--theme TwoDark\n",
+        )
+        .unwrap();
+
+        write_option(&path, "theme", "Monokai Extended").unwrap();
+
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "# Set the theme\n--theme=\"Monokai Extended\"\n--theme-light=\"GitHub\"\n--italic-text=always\n"
+        );
+
+        let _ = fs::remove_file(&path);
+        write_option(&path, "color", "bg:#eeeeee").unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "--color=\"bg:#eeeeee\"\n"
+        );
+
+        let _ = fs::remove_file(&path);
+    }
+}
@@ -1,12 +1,16 @@
 use log;
 use std::io::Error;
 use std::process::{Command, Output};
+use std::time::Duration;
 
-pub fn set(color_scheme_name: &String) -> Result<Output, Error> {
+use crate::command;
+
+pub fn set(color_scheme_name: &String, timeout: Duration) -> Result<Output, Error> {
     log::info!("Applying color scheme: {}", color_scheme_name);
-    let out = Command::new("plasma-apply-colorscheme")
-        .arg(color_scheme_name)
-        .output();
+    let out = command::output(
+        Command::new("plasma-apply-colorscheme").arg(color_scheme_name),
+        timeout,
+    );
 
     match &out {
         Ok(output) if output.status.success() => {
@@ -58,9 +62,9 @@ mod tests {
         let current = get_current_color_scheme().expect("Failed to get current wallpaper");
 
         let out = if current == config.light.color_scheme {
-            set(&config.dark.color_scheme)
+            set(&config.dark.color_scheme, Duration::from_secs(10))
         } else {
-            set(&config.light.color_scheme)
+            set(&config.light.color_scheme, Duration::from_secs(10))
         };
 
         assert!(out.is_ok());
@@ -0,0 +1,52 @@
+use std::io::Error;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+use std::time::Duration;
+
+use crate::command;
+use crate::kconfig;
+
+fn kcminputrc_path() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or(PathBuf::from("~/.config"))
+        .join("kcminputrc")
+}
+
+pub fn set(cursor_theme: &String, size: Option<u32>, timeout: Duration) -> Result<Output, Error> {
+    log::info!("Applying cursor theme: {}", cursor_theme);
+
+    // plasma-apply-cursortheme picks up the configured size, so write it first
+    if let Some(size) = size {
+        log::debug!("Setting cursor size: {}", size);
+        if let Err(e) = kconfig::write_entry(
+            &kcminputrc_path(),
+            &["Mouse"],
+            "cursorSize",
+            &size.to_string(),
+        ) {
+            log::warn!("Failed to write cursor size to kcminputrc: {}", e);
+        }
+    }
+
+    let out = command::output(
+        Command::new("plasma-apply-cursortheme").arg(cursor_theme),
+        timeout,
+    );
+
+    match &out {
+        Ok(output) if output.status.success() => {
+            log::info!("Cursor theme applied successfully: {}", cursor_theme);
+        }
+        Ok(output) => {
+            log::warn!(
+                "Failed to apply cursor theme {}: {:?}",
+                cursor_theme,
+                output.status
+            );
+        }
+        Err(e) => {
+            log::error!("Error applying cursor theme {}: {}", cursor_theme, e);
+        }
+    }
+    out
+}
@@ -0,0 +1,76 @@
+use std::error::Error;
+use std::process::Command;
+use std::time::Duration;
+
+use crate::command;
+use crate::theme::Theme;
+
+fn git_config(key: &str, value: &str, timeout: Duration) -> Result<(), Box<dyn Error>> {
+    let output = command::output(
+        Command::new("git")
+            .arg("config")
+            .arg("--global")
+            .arg(key)
+            .arg(value),
+        timeout,
+    )?;
+    match output.status.success() {
+        true => Ok(()),
+        false => Err(format!(
+            "git config {} failed: {}",
+            key,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+        .into()),
+    }
+}
+
+/// git config entries selecting the syntax theme and telling delta whether the terminal is light
+fn options(theme: &Theme, syntax_theme: &str) -> [(&'static str, String); 3] {
+    let light = *theme == Theme::Light;
+    [
+        ("delta.syntax-theme", syntax_theme.to_string()),
+        ("delta.light", light.to_string()),
+        ("delta.dark", (!light).to_string()),
+    ]
+}
+
+/// delta reads its options from the git config, so set them there.
+pub fn set(theme: &Theme, syntax_theme: &str, timeout: Duration) -> Result<(), Box<dyn Error>> {
+    log::info!("Applying delta syntax theme: {}", syntax_theme);
+
+    let result = options(theme, syntax_theme)
+        .iter()
+        .try_for_each(|(key, value)| git_config(key, value, timeout));
+
+    match &result {
+        Ok(_) => log::info!("delta syntax theme applied successfully: {}", syntax_theme),
+        Err(e) => log::error!("Failed to apply delta syntax theme {}: {}", syntax_theme, e),
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(Theme::Light, "GitHub", "true", "false")]
+    #[case(Theme::Dark, "Monokai Extended", "false", "true")]
+    fn test_options(
+        #[case] theme: Theme,
+        #[case] syntax_theme: &str,
+        #[case] light: &str,
+        #[case] dark: &str,
+    ) {
+        assert_eq!(
+            options(&theme, syntax_theme),
+            [
+                ("delta.syntax-theme", syntax_theme.to_string()),
+                ("delta.light", light.to_string()),
+                ("delta.dark", dark.to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,55 @@
+use std::env;
+use std::error::Error;
+use std::ffi::OsString;
+use std::path::PathBuf;
+
+use crate::set::bat::write_option;
+
+/// fzf only reads an options file named by `FZF_DEFAULT_OPTS_FILE`, so point that variable at
+/// this file in the shell profile.
+fn opts_path() -> PathBuf {
+    opts_path_from(env::var_os("FZF_DEFAULT_OPTS_FILE"))
+}
+
+fn opts_path_from(opts_file: Option<OsString>) -> PathBuf {
+    match opts_file {
+        Some(path) => PathBuf::from(path),
+        None => dirs::config_dir()
+            .unwrap_or(PathBuf::from("~/.config"))
+            .join("fzf/fzfrc"),
+    }
+}
+
+pub fn set(colors: &str) -> Result<(), Box<dyn Error>> {
+    log::info!("Applying fzf colors: {}", colors);
+
+    let path = opts_path();
+    match write_option(&path, "color", colors) {
+        Ok(_) => {
+            log::info!("fzf colors applied successfully: {}", colors);
+            Ok(())
+        }
+        Err(e) => {
+            log::error!(
+                "Failed to write fzf options {}: {}",
+                path.to_string_lossy(),
+                e
+            );
+            Err(e)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_opts_path_from() {
+        assert_eq!(
+            opts_path_from(Some(OsString::from("/home/me/.fzfrc"))),
+            PathBuf::from("/home/me/.fzfrc")
+        );
+        assert!(opts_path_from(None).ends_with("fzf/fzfrc"));
+    }
+}
@@ -1,12 +1,16 @@
 use log;
 use std::io::Error;
 use std::process::{Command, Output};
+use std::time::Duration;
 
-pub fn set(theme_name: &String) -> Result<Output, Error> {
+use crate::command;
+
+pub fn set(theme_name: &String, timeout: Duration) -> Result<Output, Error> {
     log::info!("Applying desktop theme: {}", theme_name);
-    let out = Command::new("plasma-apply-desktoptheme")
-        .arg(theme_name)
-        .output();
+    let out = command::output(
+        Command::new("plasma-apply-desktoptheme").arg(theme_name),
+        timeout,
+    );
 
     match &out {
         Ok(output) => {
@@ -56,9 +60,9 @@ mod tests {
         let current = get_current_desktop_theme().expect("Failed to get current wallpaper");
 
         let out = if current == config.light.desktop_theme {
-            set(&config.dark.desktop_theme)
+            set(&config.dark.desktop_theme, Duration::from_secs(10))
         } else {
-            set(&config.light.desktop_theme)
+            set(&config.light.desktop_theme, Duration::from_secs(10))
         };
 
         assert!(out.is_ok());
@@ -0,0 +1,198 @@
+use std::error::Error;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use zbus::blocking::{Connection, Proxy};
+
+use crate::command;
+use crate::kconfig;
+use crate::theme::Theme;
+
+/// GTK versions whose `settings.ini` is kept in sync
+const GTK_CONFIG_DIRS: [&str; 2] = ["gtk-3.0", "gtk-4.0"];
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct GtkOptions {
+    /// Also set `gtk-application-prefer-dark-theme` and the dconf `color-scheme` for each theme
+    #[serde(default)]
+    pub sync_color_scheme: bool,
+}
+
+/// Write the theme name and, if given, the dark preference into a GTK `settings.ini`.
+fn write_settings_ini(
+    path: &Path,
+    gtk_theme: Option<&String>,
+    prefer_dark: Option<bool>,
+) -> Result<(), Box<dyn Error>> {
+    if let Some(gtk_theme) = gtk_theme {
+        kconfig::write_entry(path, &["Settings"], "gtk-theme-name", gtk_theme)?;
+    }
+    if let Some(prefer_dark) = prefer_dark {
+        kconfig::write_entry(
+            path,
+            &["Settings"],
+            "gtk-application-prefer-dark-theme",
+            if prefer_dark { "1" } else { "0" },
+        )?;
+    }
+
+    log::debug!("Updated GTK settings at {}", path.to_string_lossy());
+    Ok(())
+}
+
+/// Ask the KDE GTK config module (kded) to apply the theme to running GTK applications.
+fn set_kde_gtk_config(gtk_theme: &String) -> zbus::Result<()> {
+    log::debug!("Setting GTK theme through kded gtkconfig module");
+    let connection = Connection::session()?;
+    let proxy = Proxy::new(
+        &connection,
+        "org.kde.kded6",
+        "/modules/gtkconfig",
+        "org.kde.GtkConfig",
+    )?;
+    proxy.call_method("setGtkTheme", &(gtk_theme))?;
+    Ok(())
+}
+
+/// Quote a string in GVariant text format, escaping backslashes and single quotes.
+fn gvariant_string(value: &str) -> String {
+    format!("'{}'", value.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+/// Write a value under the gsettings-compatible dconf path, if dconf is installed.
+fn dconf_write(key: &str, value: &str, timeout: Duration) -> Result<(), String> {
+    match command::output(
+        Command::new("dconf")
+            .arg("write")
+            .arg(key)
+            .arg(gvariant_string(value)),
+        timeout,
+    ) {
+        Ok(output) if output.status.success() => {
+            log::debug!("dconf {} set to {}", key, value);
+            Ok(())
+        }
+        Ok(output) => Err(format!(
+            "dconf write {} failed: {}: {}",
+            key,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )),
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            log::debug!("dconf not available, skipping {}", key);
+            Ok(())
+        }
+        Err(e) => Err(format!("dconf write {} failed: {}", key, e)),
+    }
+}
+
+/// Apply the GTK theme, and the dark preference when `options.sync_color_scheme` is set.
+///
+/// Every target is tried even if an earlier one failed; the error lists all that failed.
+pub fn set(
+    theme: &Theme,
+    gtk_theme: Option<&String>,
+    options: &GtkOptions,
+    timeout: Duration,
+) -> Result<(), Box<dyn Error>> {
+    log::info!("Applying GTK settings for theme: {}", theme.to_string());
+    let prefer_dark = options.sync_color_scheme.then_some(*theme == Theme::Dark);
+    let mut failures = Vec::new();
+
+    // settings.ini for GTK 3 and GTK 4
+    let config_dir = dirs::config_dir().unwrap_or(PathBuf::from("~/.config"));
+    for dir in GTK_CONFIG_DIRS {
+        let path = config_dir.join(dir).join("settings.ini");
+        if let Err(e) = write_settings_ini(&path, gtk_theme, prefer_dark) {
+            failures.push(format!(
+                "Failed to update GTK settings at {}: {}",
+                path.to_string_lossy(),
+                e
+            ));
+        }
+    }
+
+    // KDE GTK config module, which applies the theme to running GTK applications
+    if let Some(gtk_theme) = gtk_theme {
+        match set_kde_gtk_config(gtk_theme) {
+            Ok(_) => log::debug!("kded gtkconfig updated to {}", gtk_theme),
+            Err(e) => failures.push(format!("Failed to set GTK theme through kded: {}", e)),
+        }
+    }
+
+    // org.gnome.desktop.interface for GTK 4/libadwaita and portal consumers
+    if let Some(prefer_dark) = prefer_dark {
+        let color_scheme = match prefer_dark {
+            true => "prefer-dark",
+            false => "prefer-light",
+        };
+        if let Err(e) = dconf_write(
+            "/org/gnome/desktop/interface/color-scheme",
+            color_scheme,
+            timeout,
+        ) {
+            failures.push(e);
+        }
+    }
+    if let Some(gtk_theme) = gtk_theme
+        && let Err(e) = dconf_write("/org/gnome/desktop/interface/gtk-theme", gtk_theme, timeout)
+    {
+        failures.push(e);
+    }
+
+    if !failures.is_empty() {
+        for failure in failures.iter() {
+            log::error!("{}", failure);
+        }
+        return Err(failures.join("; ").into());
+    }
+
+    log::info!("GTK settings applied for theme: {}", theme.to_string());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use std::env::temp_dir;
+    use std::fs;
+
+    #[test]
+    fn test_write_settings_ini() {
+        let path = temp_dir().join("kswitch_test_gtk/settings.ini");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(
+            &path,
+            "[Settings]\ngtk-theme-name=Breeze\ngtk-font-name=Noto Sans,  10\n",
+        )
+        .unwrap();
+
+        write_settings_ini(&path, Some(&String::from("Breeze-Dark")), None).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "[Settings]\ngtk-theme-name=Breeze-Dark\ngtk-font-name=Noto Sans,  10\n"
+        );
+
+        write_settings_ini(&path, None, Some(true)).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "[Settings]\ngtk-theme-name=Breeze-Dark\ngtk-font-name=Noto Sans,  10\ngtk-application-prefer-dark-theme=1\n"
+        );
+
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[rstest]
+    #[case("prefer-dark", "'prefer-dark'")]
+    #[case("Tom's Theme", "'Tom\\'s Theme'")]
+    #[case("back\\slash", "'back\\\\slash'")]
+    fn test_gvariant_string(#[case] value: &str, #[case] expected: &str) {
+        assert_eq!(gvariant_string(value), expected);
+    }
+}
@@ -0,0 +1,60 @@
+use std::error::Error;
+use std::path::PathBuf;
+
+use zbus::blocking::Connection;
+use zbus::names::BusName;
+
+use crate::kconfig;
+
+/// `KGlobalSettings::ChangeType::IconChanged`
+const ICON_CHANGED: i32 = 4;
+/// Number of `KIconLoader::Group`s (Desktop, Toolbar, MainToolbar, Small, Panel, Dialog)
+const ICON_GROUPS: i32 = 6;
+
+fn kdeglobals_path() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or(PathBuf::from("~/.config"))
+        .join("kdeglobals")
+}
+
+/// Tell running KDE applications to reload their icons, like the icons KCM does.
+fn notify_icon_change() -> zbus::Result<()> {
+    log::debug!("Emitting KGlobalSettings notifyChange for icon groups");
+    let connection = Connection::session()?;
+
+    for group in 0..ICON_GROUPS {
+        connection.emit_signal(
+            None::<BusName>,
+            "/KGlobalSettings",
+            "org.kde.KGlobalSettings",
+            "notifyChange",
+            &(ICON_CHANGED, group),
+        )?;
+    }
+    Ok(())
+}
+
+pub fn set(icon_theme: &String) -> Result<(), Box<dyn Error>> {
+    log::info!("Applying icon theme: {}", icon_theme);
+
+    // Write the icon theme to kdeglobals
+    if let Err(e) = kconfig::write_entry(&kdeglobals_path(), &["Icons"], "Theme", icon_theme) {
+        log::error!("Failed to write icon theme to kdeglobals: {}", e);
+        return Err(e);
+    }
+
+    // Notify running applications so they refresh their icons
+    match notify_icon_change() {
+        Ok(_) => {
+            log::info!("Icon theme applied successfully: {}", icon_theme);
+            Ok(())
+        }
+        Err(e) => {
+            log::warn!(
+                "Icon theme written but failed to notify applications: {}",
+                e
+            );
+            Err(Box::new(e))
+        }
+    }
+}
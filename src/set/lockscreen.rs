@@ -0,0 +1,77 @@
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+use crate::kconfig;
+use crate::set::wallpaper::{file_uri, validate};
+
+fn kscreenlockerrc_path() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or(PathBuf::from("~/.config"))
+        .join("kscreenlockerrc")
+}
+
+/// Write the lock screen wallpaper into a kscreenlockerrc file.
+fn write_wallpaper(kscreenlockerrc: &Path, wallpaper: &Path) -> Result<(), Box<dyn Error>> {
+    kconfig::write_entry(
+        kscreenlockerrc,
+        &["Greeter"],
+        "WallpaperPlugin",
+        "org.kde.image",
+    )?;
+    kconfig::write_entry(
+        kscreenlockerrc,
+        &["Greeter", "Wallpaper", "org.kde.image", "General"],
+        "Image",
+        &file_uri(wallpaper),
+    )?;
+    Ok(())
+}
+
+pub fn set(wallpaper: &Path) -> Result<(), Box<dyn Error>> {
+    log::info!(
+        "Applying lock screen wallpaper: {}",
+        wallpaper.to_string_lossy()
+    );
+
+    if let Err(e) = validate(wallpaper) {
+        log::error!("Invalid lock screen wallpaper: {}", e);
+        return Err(Box::new(e));
+    }
+
+    // The greeter reads its config when the screen is locked, so no notification is needed
+    match write_wallpaper(&kscreenlockerrc_path(), wallpaper) {
+        Ok(_) => {
+            log::info!(
+                "Lock screen wallpaper applied successfully: {}",
+                wallpaper.to_string_lossy()
+            );
+            Ok(())
+        }
+        Err(e) => {
+            log::error!("Failed to write lock screen wallpaper: {}", e);
+            Err(e)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env::temp_dir;
+    use std::fs;
+
+    #[test]
+    fn test_write_wallpaper() {
+        let path = temp_dir().join("kswitch_test_kscreenlockerrc");
+        fs::write(&path, "[Daemon]\nTimeout=10\n").unwrap();
+
+        write_wallpaper(&path, Path::new("/w/night.png")).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "[Daemon]\nTimeout=10\n\n[Greeter]\nWallpaperPlugin=org.kde.image\n\n[Greeter][Wallpaper][org.kde.image][General]\nImage=file:///w/night.png\n"
+        );
+
+        let _ = fs::remove_file(&path);
+    }
+}
@@ -1,5 +1,80 @@
+pub mod app_color_scheme;
+pub mod bat;
 pub mod color_scheme;
+pub mod cursor_theme;
+pub mod delta;
+pub mod fzf;
 pub mod global_theme;
+pub mod gtk;
+pub mod icon_theme;
 pub mod konsole;
+pub mod lockscreen;
+pub mod neovim;
+pub mod portal;
 pub mod terminal;
+pub mod tmux;
+pub mod vscode;
 pub mod wallpaper;
+pub mod wallpaper_package;
+pub mod window_decoration;
+
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+/// Run `update` for the socket of every running instance of `program`. Sockets of instances
+/// that exited or crashed stay behind, so this keeps going after a failure and only fails when
+/// no instance could be updated. Returns how many were.
+pub fn update_sockets(
+    program: &str,
+    sockets: &[PathBuf],
+    update: impl Fn(&Path) -> Result<(), Box<dyn Error>>,
+) -> Result<usize, Box<dyn Error>> {
+    let mut failures = Vec::new();
+    for socket in sockets.iter() {
+        match update(socket) {
+            Ok(_) => log::debug!("{} at {} updated", program, socket.to_string_lossy()),
+            Err(e) => {
+                log::warn!(
+                    "Failed to update {} at {}: {}",
+                    program,
+                    socket.to_string_lossy(),
+                    e
+                );
+                failures.push(socket.to_string_lossy().to_string());
+            }
+        }
+    }
+
+    match failures.len() < sockets.len() {
+        true => Ok(sockets.len() - failures.len()),
+        false => Err(format!("Failed to update {} at {}", program, failures.join(", ")).into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(&["/run/a", "/run/b"], &[], Ok(2))]
+    #[case(&["/run/a", "/run/b"], &["/run/a"], Ok(1))]
+    #[case(&["/run/a", "/run/b"], &["/run/a", "/run/b"], Err("Failed to update tmux at /run/a, /run/b"))]
+    fn test_update_sockets(
+        #[case] sockets: &[&str],
+        #[case] stale: &[&str],
+        #[case] expected: Result<usize, &str>,
+    ) {
+        let sockets: Vec<PathBuf> = sockets.iter().map(PathBuf::from).collect();
+        let result = update_sockets("tmux", &sockets, |socket| {
+            match stale.iter().any(|s| Path::new(s) == socket) {
+                true => Err("connection refused".into()),
+                false => Ok(()),
+            }
+        });
+        assert_eq!(
+            result.map_err(|e| e.to_string()),
+            expected.map_err(String::from)
+        );
+    }
+}
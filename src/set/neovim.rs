@@ -0,0 +1,112 @@
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::os::unix::fs::FileTypeExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+
+use crate::command;
+use crate::set::update_sockets;
+use crate::theme::{Neovim, Theme};
+
+/// Directories Neovim creates its default RPC sockets in: `$XDG_RUNTIME_DIR/nvim.<pid>.0`, or
+/// `$TMPDIR/nvim.<user>/<random>/nvim.<pid>.0` without a runtime dir.
+fn socket_dirs() -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    if let Some(runtime_dir) = dirs::runtime_dir() {
+        dirs.push(runtime_dir);
+    }
+    let tmp = env::temp_dir();
+    if let Ok(user) = env::var("USER") {
+        let user_dir = tmp.join(format!("nvim.{}", user));
+        if let Ok(entries) = fs::read_dir(&user_dir) {
+            dirs.extend(
+                entries
+                    .filter_map(|entry| entry.ok().map(|e| e.path()))
+                    .filter(|path| path.is_dir()),
+            );
+        }
+    }
+    dirs
+}
+
+/// Sockets of running Neovim instances
+fn sockets(dirs: &[PathBuf]) -> Vec<PathBuf> {
+    let mut sockets: Vec<PathBuf> = dirs
+        .iter()
+        .filter_map(|dir| fs::read_dir(dir).ok())
+        .flat_map(|entries| entries.filter_map(|entry| entry.ok()))
+        .filter(|entry| {
+            entry.file_name().to_string_lossy().starts_with("nvim.")
+                && entry.file_type().is_ok_and(|t| t.is_socket())
+        })
+        .map(|entry| entry.path())
+        .collect();
+    sockets.sort();
+    sockets
+}
+
+/// Vim script run in every instance, quoted for `execute()`
+fn expression(theme: &Theme, neovim: &Neovim) -> String {
+    let mut commands = format!("set background={}", theme.to_string());
+    if let Some(colorscheme) = &neovim.colorscheme {
+        commands.push_str(&format!(" | colorscheme {}", colorscheme));
+    }
+    // Single quoted Vim strings escape quotes by doubling them
+    format!("execute('{}')", commands.replace('\'', "''"))
+}
+
+fn send(socket: &Path, expression: &str, timeout: Duration) -> Result<(), Box<dyn Error>> {
+    let output = command::output(
+        Command::new("nvim")
+            .arg("--server")
+            .arg(socket)
+            .arg("--remote-expr")
+            .arg(expression),
+        timeout,
+    )?;
+    match output.status.success() {
+        true => Ok(()),
+        false => Err(String::from_utf8_lossy(&output.stderr).trim().into()),
+    }
+}
+
+pub fn set(theme: &Theme, neovim: &Neovim, timeout: Duration) -> Result<(), Box<dyn Error>> {
+    log::info!("Applying Neovim background: {}", theme.to_string());
+
+    let sockets = sockets(&socket_dirs());
+    if sockets.is_empty() {
+        log::debug!("No running Neovim instances found");
+        return Ok(());
+    }
+
+    let expression = expression(theme, neovim);
+    let updated = update_sockets("Neovim", &sockets, |socket| {
+        send(socket, &expression, timeout)
+    })?;
+    log::info!("Neovim background applied to {} instance(s)", updated);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expression() {
+        assert_eq!(
+            expression(&Theme::Dark, &Neovim::default()),
+            "execute('set background=dark')"
+        );
+        assert_eq!(
+            expression(
+                &Theme::Light,
+                &Neovim {
+                    colorscheme: Some(String::from("it's-light")),
+                }
+            ),
+            "execute('set background=light | colorscheme it''s-light')"
+        );
+    }
+}
@@ -0,0 +1,54 @@
+use std::io::{Error, ErrorKind};
+use std::time::Duration;
+
+use zbus::blocking::Connection;
+
+use crate::get::portal::{self, ColorSchemePreference};
+use crate::theme::Theme;
+
+/// How long the portal gets to pick up a change before it counts as a mismatch
+const PORTAL_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Make sure the portal-visible `color-scheme` preference matches the applied theme.
+///
+/// xdg-desktop-portal-kde derives `color-scheme` from the palette plasma-apply-colorscheme
+/// just applied, so there is nothing to write here, only a change to wait for. Fails with
+/// [`ErrorKind::TimedOut`] if the portal still shows another preference after
+/// [`PORTAL_TIMEOUT`].
+pub fn set(theme: &Theme) -> Result<(), Error> {
+    log::info!(
+        "Verifying portal color-scheme for theme: {}",
+        theme.to_string()
+    );
+    let expected = ColorSchemePreference::for_theme(theme);
+
+    let connection = Connection::session().map_err(|e| {
+        log::warn!("Failed to connect to session bus: {}", e);
+        Error::other(e)
+    })?;
+
+    match portal::wait_for(&connection, theme, PORTAL_TIMEOUT) {
+        Ok(preference) if preference == expected => {
+            log::info!("Portal color-scheme is {}", preference);
+            Ok(())
+        }
+        Ok(preference) => {
+            log::warn!(
+                "Portal color-scheme is still {}, expected {}",
+                preference,
+                expected
+            );
+            Err(Error::new(
+                ErrorKind::TimedOut,
+                format!(
+                    "portal color-scheme is still {}, expected {}",
+                    preference, expected
+                ),
+            ))
+        }
+        Err(e) => {
+            log::warn!("Failed to read portal color-scheme: {}", e);
+            Err(Error::other(e))
+        }
+    }
+}
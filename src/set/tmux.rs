@@ -0,0 +1,105 @@
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::os::unix::fs::FileTypeExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+
+use crate::command;
+use crate::set::update_sockets;
+
+/// tmux keeps one socket per server in `$TMUX_TMPDIR/tmux-<uid>/` (`/tmp` by default).
+fn socket_dir() -> PathBuf {
+    let tmp = env::var_os("TMUX_TMPDIR")
+        .map(PathBuf::from)
+        .unwrap_or(PathBuf::from("/tmp"));
+    // SAFETY: getuid cannot fail and has no side effects
+    let uid = unsafe { libc::getuid() };
+    tmp.join(format!("tmux-{}", uid))
+}
+
+/// Sockets of all tmux servers, e.g. `default` and those started with `-L <name>`
+fn sockets(dir: &Path) -> Vec<PathBuf> {
+    let mut sockets: Vec<PathBuf> = match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_ok_and(|t| t.is_socket()))
+            .map(|entry| entry.path())
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    sockets.sort();
+    sockets
+}
+
+fn source_file(socket: &Path, theme_file: &Path, timeout: Duration) -> Result<(), Box<dyn Error>> {
+    let output = command::output(
+        Command::new("tmux")
+            .arg("-S")
+            .arg(socket)
+            .arg("source-file")
+            .arg(theme_file),
+        timeout,
+    )?;
+    match output.status.success() {
+        true => Ok(()),
+        false => Err(String::from_utf8_lossy(&output.stderr).trim().into()),
+    }
+}
+
+pub fn set(theme_file: &Path, timeout: Duration) -> Result<(), Box<dyn Error>> {
+    log::info!("Applying tmux theme: {}", theme_file.to_string_lossy());
+
+    if !theme_file.is_file() {
+        return Err(format!("tmux theme not found: {}", theme_file.to_string_lossy()).into());
+    }
+
+    let sockets = sockets(&socket_dir());
+    if sockets.is_empty() {
+        log::debug!("No running tmux servers found");
+        return Ok(());
+    }
+
+    let updated = update_sockets("tmux", &sockets, |socket| {
+        source_file(socket, theme_file, timeout)
+    })?;
+    log::info!("tmux theme applied to {} server(s)", updated);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env::temp_dir;
+    use std::os::unix::net::UnixListener;
+
+    #[test]
+    fn test_sockets() {
+        let dir = temp_dir().join("kswitch_test_tmux");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let _work = UnixListener::bind(dir.join("work")).unwrap();
+        let _default = UnixListener::bind(dir.join("default")).unwrap();
+        fs::write(dir.join("notes"), "not a socket").unwrap();
+
+        assert_eq!(sockets(&dir), vec![dir.join("default"), dir.join("work")]);
+        assert!(sockets(&dir.join("missing")).is_empty());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_set_missing_theme_file() {
+        let result = set(
+            &temp_dir().join("kswitch_test_tmux_missing.conf"),
+            Duration::from_secs(1),
+        );
+        assert!(
+            result
+                .unwrap_err()
+                .to_string()
+                .starts_with("tmux theme not found")
+        );
+    }
+}
@@ -0,0 +1,171 @@
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const COLOR_THEME_KEY: &str = "\"workbench.colorTheme\"";
+
+/// User settings of VS Code and its rebuilds that exist on this machine
+fn settings_paths() -> Vec<PathBuf> {
+    let config_dir = dirs::config_dir().unwrap_or(PathBuf::from("~/.config"));
+    ["Code", "Code - OSS", "Code - Insiders", "VSCodium"]
+        .iter()
+        .map(|app| config_dir.join(app).join("User/settings.json"))
+        .filter(|path| path.is_file())
+        .collect()
+}
+
+/// Byte offset of the quote closing the JSON string that opens at `start`
+fn string_end(text: &str, start: usize) -> Option<usize> {
+    let mut escaped = false;
+    for (i, c) in text[start + 1..].char_indices() {
+        match c {
+            '\\' if !escaped => escaped = true,
+            '"' if !escaped => return Some(start + 1 + i),
+            _ => escaped = false,
+        }
+    }
+    None
+}
+
+/// Byte range of the JSON string value of `key` in the top-level object, quotes included.
+///
+/// Keys inside nested objects, such as language-specific `"[python]"` blocks, and comments are
+/// skipped.
+fn string_value(contents: &str, key: &str) -> Option<(usize, usize)> {
+    let mut depth = 0;
+    let mut i = 0;
+    while i < contents.len() {
+        let rest = &contents[i..];
+        if rest.starts_with("//") {
+            i += rest.find('\n').unwrap_or(rest.len());
+            continue;
+        }
+        if rest.starts_with("/*") {
+            i += rest.find("*/").map_or(rest.len(), |end| end + 2);
+            continue;
+        }
+        match rest.chars().next()? {
+            '{' | '[' => depth += 1,
+            '}' | ']' => depth -= 1,
+            '"' => {
+                let end = string_end(contents, i)?;
+                if depth == 1 && &contents[i..=end] == key {
+                    let after_key = contents[end + 1..].trim_start();
+                    let value = after_key.strip_prefix(':')?.trim_start();
+                    if value.starts_with('"') {
+                        let start = contents.len() - value.len();
+                        return Some((start, string_end(contents, start)? + 1));
+                    }
+                }
+                i = end;
+            }
+            _ => {}
+        }
+        i += rest.chars().next()?.len_utf8();
+    }
+    None
+}
+
+/// Set `workbench.colorTheme` in the text of a settings.json.
+///
+/// settings.json allows comments and trailing commas, so it is edited as text instead of being
+/// parsed, which also keeps the user's formatting.
+fn set_color_theme(contents: &str, theme: &str) -> Result<String, Box<dyn Error>> {
+    let value = serde_json::to_string(theme)?;
+
+    if let Some((start, end)) = string_value(contents, COLOR_THEME_KEY) {
+        let mut updated = contents.to_string();
+        updated.replace_range(start..end, &value);
+        return Ok(updated);
+    }
+
+    // Not set yet, so add it as the first entry
+    let brace = contents
+        .find('{')
+        .ok_or("settings.json does not contain an object")?;
+    let (before, after) = contents.split_at(brace + 1);
+    let entry = format!("\n    {}: {}", COLOR_THEME_KEY, value);
+    match after.trim_start().starts_with('}') {
+        true => Ok(format!("{}{}\n{}", before, entry, after.trim_start())),
+        false => Ok(format!("{}{},{}", before, entry, after)),
+    }
+}
+
+fn write_color_theme(settings: &Path, theme: &str) -> Result<(), Box<dyn Error>> {
+    let contents = fs::read_to_string(settings)?;
+    let updated = set_color_theme(&contents, theme)?;
+    if updated != contents {
+        fs::write(settings, updated)?;
+    }
+    Ok(())
+}
+
+/// VS Code watches its settings, so running windows switch as soon as the file is written.
+pub fn set(theme: &str) -> Result<(), Box<dyn Error>> {
+    log::info!("Applying VS Code color theme: {}", theme);
+
+    let paths = settings_paths();
+    if paths.is_empty() {
+        log::debug!("No VS Code settings found");
+        return Ok(());
+    }
+
+    for path in paths.iter() {
+        match write_color_theme(path, theme) {
+            Ok(_) => log::debug!("Updated {}", path.to_string_lossy()),
+            Err(e) => {
+                log::error!(
+                    "Failed to update VS Code settings {}: {}",
+                    path.to_string_lossy(),
+                    e
+                );
+                return Err(e);
+            }
+        }
+    }
+
+    log::info!("VS Code color theme applied successfully: {}", theme);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::replace(
+        "{\n    \"workbench.colorTheme\": \"Default Light Modern\",\n    \"editor.fontSize\": 14\n}\n",
+        "{\n    \"workbench.colorTheme\": \"Default Dark Modern\",\n    \"editor.fontSize\": 14\n}\n"
+    )]
+    #[case::skip_comments(
+        "{\n    // \"workbench.colorTheme\": \"Solarized\",\n    \"workbench.colorTheme\": \"Quiet \\\"Light\\\"\", // mine\n}\n",
+        "{\n    // \"workbench.colorTheme\": \"Solarized\",\n    \"workbench.colorTheme\": \"Default Dark Modern\", // mine\n}\n"
+    )]
+    #[case::skip_language_blocks(
+        "{\n    \"[markdown]\": {\n        \"workbench.colorTheme\": \"Solarized Light\"\n    },\n    \"workbench.colorTheme\": \"Default Light Modern\"\n}\n",
+        "{\n    \"[markdown]\": {\n        \"workbench.colorTheme\": \"Solarized Light\"\n    },\n    \"workbench.colorTheme\": \"Default Dark Modern\"\n}\n"
+    )]
+    #[case::insert_beside_language_blocks(
+        "{\n    \"[markdown]\": { \"workbench.colorTheme\": \"Solarized Light\" }\n}\n",
+        "{\n    \"workbench.colorTheme\": \"Default Dark Modern\",\n    \"[markdown]\": { \"workbench.colorTheme\": \"Solarized Light\" }\n}\n"
+    )]
+    #[case::skip_block_comments(
+        "{\n    /* \"workbench.colorTheme\": \"Solarized\" */\n    \"workbench.colorTheme\": \"Quiet Light\"\n}\n",
+        "{\n    /* \"workbench.colorTheme\": \"Solarized\" */\n    \"workbench.colorTheme\": \"Default Dark Modern\"\n}\n"
+    )]
+    #[case::insert(
+        "{\n    \"editor.fontSize\": 14\n}\n",
+        "{\n    \"workbench.colorTheme\": \"Default Dark Modern\",\n    \"editor.fontSize\": 14\n}\n"
+    )]
+    #[case::empty(
+        "{}\n",
+        "{\n    \"workbench.colorTheme\": \"Default Dark Modern\"\n}\n"
+    )]
+    fn test_set_color_theme(#[case] contents: &str, #[case] expected: &str) {
+        assert_eq!(
+            set_color_theme(contents, "Default Dark Modern").unwrap(),
+            expected
+        );
+    }
+}
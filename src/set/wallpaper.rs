@@ -1,39 +1,521 @@
 use log;
-use std::io::Error;
+#[cfg(test)]
+use std::ffi::OsStr;
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::os::unix::ffi::OsStrExt;
 use std::os::unix::process::ExitStatusExt;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::Output;
+use std::time::{SystemTime, UNIX_EPOCH};
 
+use image::ImageReader;
 use zbus::Result as ZbusResult;
 use zbus::blocking::Connection;
 
-pub fn set(wallpaper: &PathBuf) -> Result<Output, Error> {
-    log::info!("Applying wallpaper: {}", wallpaper.to_string_lossy());
+use crate::set::wallpaper_package::{self, Candidate};
+use crate::state::StateManager;
+use crate::theme::{SelectionMode, Theme, Wallpaper, WallpaperDirectory};
 
-    // Convert the path to a file:// URI
-    let wallpaper_uri = format!("file://{}", wallpaper.display());
+/// Key matching every desktop in a per-desktop wallpaper table
+const FALLBACK_KEY: &str = "*";
 
-    // JavaScript script sent to plasmashell via D-Bus
-    let script = format!(
-        "var Desktops = desktops();
-         for (i = 0; i < Desktops.length; i++) {{
-             d = Desktops[i];
-             d.wallpaperPlugin = 'org.kde.image';
-             d.currentConfigGroup = Array('Wallpaper', 'org.kde.image', 'General');
-             d.writeConfig('Image', '{}');
+/// Screen a wallpaper entry is restricted to
+#[derive(Debug, PartialEq)]
+enum Screen {
+    Index(u32),
+    Connector(String),
+}
+
+/// Desktops a wallpaper entry applies to; `None` matches any screen or activity
+#[derive(Debug, PartialEq)]
+struct Target {
+    screen: Option<Screen>,
+    activity: Option<String>,
+}
+
+/// Activity ids are UUIDs, e.g. `5ba6b6a1-8f41-4b4d-a8d6-0c0a6c3d5e1f`
+fn is_activity_id(key: &str) -> bool {
+    let groups: Vec<&str> = key.split('-').collect();
+    groups.iter().map(|g| g.len()).eq([8, 4, 4, 4, 12])
+        && groups
+            .iter()
+            .all(|g| g.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+fn parse_screen(key: &str) -> Screen {
+    match key.parse::<u32>() {
+        Ok(index) => Screen::Index(index),
+        Err(_) => Screen::Connector(key.to_string()),
+    }
+}
+
+/// Parse a per-desktop wallpaper key: screen index, connector name, activity id,
+/// `<screen>@<activity>` or the `*` fallback.
+fn parse_target(key: &str) -> Target {
+    let key = key.trim();
+    if key == FALLBACK_KEY {
+        Target {
+            screen: None,
+            activity: None,
+        }
+    } else if let Some((screen, activity)) = key.split_once('@') {
+        Target {
+            screen: Some(parse_screen(screen)),
+            activity: Some(activity.to_string()),
+        }
+    } else if is_activity_id(key) {
+        Target {
+            screen: None,
+            activity: Some(key.to_string()),
+        }
+    } else {
+        Target {
+            screen: Some(parse_screen(key)),
+            activity: None,
+        }
+    }
+}
+
+/// File extensions considered images when picking from a wallpaper directory
+const IMAGE_EXTENSIONS: [&str; 9] = [
+    "png", "jpg", "jpeg", "webp", "bmp", "svg", "svgz", "avif", "jxl",
+];
+
+/// Formats Plasma can show but the image crate cannot decode; only their existence is checked
+const UNVERIFIED_EXTENSIONS: [&str; 4] = ["svg", "svgz", "avif", "jxl"];
+
+/// Quote a string as a single-quoted JavaScript literal.
+fn js_string(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('\'');
+    for c in value.chars() {
+        match c {
+            '\\' => quoted.push_str("\\\\"),
+            '\'' => quoted.push_str("\\'"),
+            '"' => quoted.push_str("\\\""),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            // line and paragraph separators terminate string literals in older engines
+            '\u{2028}' | '\u{2029}' => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('\'');
+    quoted
+}
+
+/// Convert a local path to a percent-encoded `file://` URI.
+pub fn file_uri(path: &Path) -> String {
+    let mut uri = String::from("file://");
+    for byte in path.as_os_str().as_bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                uri.push(*byte as char)
+            }
+            _ => uri.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    uri
+}
+
+/// Check that a wallpaper exists and is an image Plasma will be able to show.
+pub fn validate(path: &Path) -> Result<(), Error> {
+    if !path.is_file() {
+        return Err(Error::new(
+            ErrorKind::NotFound,
+            format!("Wallpaper not found: {}", path.to_string_lossy()),
+        ));
+    }
+
+    let unverified = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| UNVERIFIED_EXTENSIONS.contains(&ext.to_lowercase().as_str()));
+    if unverified {
+        return Ok(());
+    }
+
+    ImageReader::open(path)?
+        .with_guessed_format()?
+        .into_dimensions()
+        .map(|_| ())
+        .map_err(|e| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "Wallpaper is not a decodable image: {}: {}",
+                    path.to_string_lossy(),
+                    e
+                ),
+            )
+        })
+}
+
+/// What a desktop ends up showing
+#[derive(Debug, PartialEq)]
+enum Source {
+    Image(PathBuf),
+    Slideshow {
+        directory: PathBuf,
+        interval: u64,
+    },
+    /// Images of a wallpaper package; plasmashell picks one per screen resolution
+    Package {
+        name: String,
+        candidates: Vec<Candidate>,
+    },
+}
+
+/// Images directly inside `directory`, sorted so round-robin order is stable.
+fn list_images(directory: &Path) -> Vec<PathBuf> {
+    let mut images: Vec<PathBuf> = match fs::read_dir(directory) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| {
+                path.is_file()
+                    && path
+                        .extension()
+                        .and_then(|ext| ext.to_str())
+                        .is_some_and(|ext| IMAGE_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+            })
+            .collect(),
+        Err(e) => {
+            log::error!(
+                "Failed to read wallpaper directory {}: {}",
+                directory.to_string_lossy(),
+                e
+            );
+            Vec::new()
+        }
+    };
+    images.sort();
+    images
+}
+
+/// Pick the next image: the one after `last` for round-robin, otherwise a random one
+/// derived from `seed` that avoids repeating `last` when there is a choice.
+fn pick(
+    images: &[PathBuf],
+    mode: &SelectionMode,
+    last: Option<&PathBuf>,
+    seed: u64,
+) -> Option<PathBuf> {
+    if images.is_empty() {
+        return None;
+    }
+    let last_index = last.and_then(|last| images.iter().position(|i| i == last));
+
+    let index = match mode {
+        SelectionMode::RoundRobin => last_index.map_or(0, |i| (i + 1) % images.len()),
+        _ => {
+            let index = (seed % images.len() as u64) as usize;
+            if Some(index) == last_index && images.len() > 1 {
+                (index + 1) % images.len()
+            } else {
+                index
+            }
+        }
+    };
+    Some(images[index].clone())
+}
+
+fn seed() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or_default()
+}
+
+/// Pick an image from a directory, remembering the pick in the state file.
+fn pick_from_directory(directory: &Path, mode: &SelectionMode) -> Option<PathBuf> {
+    let key = directory.to_string_lossy().to_string();
+    let state_manager = StateManager::new().ok();
+    let last = state_manager
+        .as_ref()
+        .and_then(|state_manager| state_manager.load().ok())
+        .and_then(|state| state.wallpaper_picks.get(&key).cloned());
+
+    let image = pick(&list_images(directory), mode, last.as_ref(), seed());
+    match &image {
+        Some(image) => {
+            log::debug!("Picked wallpaper {} from {}", image.to_string_lossy(), key);
+            if let Some(state_manager) = state_manager {
+                let picked = image.clone();
+                if let Err(e) = state_manager.update(|state| {
+                    state.wallpaper_picks.insert(key, picked);
+                }) {
+                    log::warn!("Failed to remember wallpaper pick: {}", e);
+                }
+            }
+        }
+        None => log::warn!("No images found in wallpaper directory {}", key),
+    }
+    image
+}
+
+/// Resolve a configured path: images are used as is, wallpaper packages offer their images
+/// for the theme, and other directories get a random pick.
+fn resolve(path: &Path, theme: &Theme) -> Option<Source> {
+    if wallpaper_package::is_package(path) {
+        Some(Source::Package {
+            name: wallpaper_package::name(path),
+            candidates: wallpaper_package::candidates(path, theme),
+        })
+    } else if path.is_dir() {
+        pick_from_directory(path, &SelectionMode::Random).map(Source::Image)
+    } else {
+        Some(Source::Image(path.to_path_buf()))
+    }
+}
+
+fn resolve_directory(directory: &WallpaperDirectory) -> Option<Source> {
+    match directory.mode {
+        SelectionMode::Slideshow => Some(Source::Slideshow {
+            directory: directory.directory.clone(),
+            interval: directory.interval,
+        }),
+        _ => pick_from_directory(&directory.directory, &directory.mode).map(Source::Image),
+    }
+}
+
+fn targets(wallpaper: &Wallpaper, theme: &Theme) -> Vec<(Target, Source)> {
+    match wallpaper {
+        Wallpaper::Single(path) => resolve(path, theme)
+            .map(|source| (parse_target(FALLBACK_KEY), source))
+            .into_iter()
+            .collect(),
+        Wallpaper::Directory(directory) => resolve_directory(directory)
+            .map(|source| (parse_target(FALLBACK_KEY), source))
+            .into_iter()
+            .collect(),
+        Wallpaper::PerDesktop(map) => map
+            .iter()
+            .filter_map(|(key, path)| {
+                resolve(path, theme).map(|source| (parse_target(key), source))
+            })
+            .collect(),
+    }
+}
+
+/// Build the plasmashell script applying each entry to the desktops it targets.
+///
+/// Every desktop of every activity gets the most specific matching entry: screen and
+/// activity, then screen only, then activity only, then the fallback. For packages the
+/// image closest to the screen's aspect ratio is used, preferring the smallest one that
+/// does not need upscaling. Desktops without a screen get the largest image.
+fn build_script(entries: &[(Target, Source)]) -> String {
+    let entries: Vec<String> = entries
+        .iter()
+        .map(|(target, source)| {
+            let (index, connector) = match &target.screen {
+                Some(Screen::Index(index)) => (index.to_string(), String::from("null")),
+                Some(Screen::Connector(connector)) => (String::from("null"), js_string(connector)),
+                None => (String::from("null"), String::from("null")),
+            };
+            let activity = match &target.activity {
+                Some(activity) => js_string(activity),
+                None => String::from("null"),
+            };
+            let (plugin, value, interval) = match source {
+                Source::Image(path) => ("org.kde.image", js_string(&file_uri(path)), 0),
+                Source::Slideshow {
+                    directory,
+                    interval,
+                } => (
+                    // The slideshow takes plain directory paths, not URLs
+                    "org.kde.slideshow",
+                    js_string(&directory.to_string_lossy()),
+                    *interval,
+                ),
+                Source::Package { candidates, .. } => {
+                    let candidates: Vec<String> = candidates
+                        .iter()
+                        .map(|c| {
+                            format!(
+                                "[{}, {}, {}]",
+                                c.width,
+                                c.height,
+                                js_string(&file_uri(&c.path))
+                            )
+                        })
+                        .collect();
+                    ("org.kde.image", format!("[{}]", candidates.join(", ")), 0)
+                }
+            };
+            format!(
+                "[{}, {}, {}, {}, {}, {}]",
+                index,
+                connector,
+                activity,
+                js_string(plugin),
+                value,
+                interval
+            )
+        })
+        .collect();
+
+    format!(
+        "var entries = [{}];
+         function largestImage(candidates) {{
+             var best = candidates[0];
+             for (k = 1; k < candidates.length; k++) {{
+                 if (candidates[k][0] * candidates[k][1] > best[0] * best[1]) best = candidates[k];
+             }}
+             return best[2];
+         }}
+         function bestImage(candidates, screen) {{
+             var g = screen >= 0 ? screenGeometry(screen) : null;
+             if (!g || !(g.width > 0) || !(g.height > 0)) return largestImage(candidates);
+             var ratio = g.width / g.height;
+             var best = null;
+             var bestAspect = 0;
+             var bestScale = 0;
+             for (k = 0; k < candidates.length; k++) {{
+                 c = candidates[k];
+                 aspect = Math.round(Math.abs(c[0] / c[1] - ratio) * 100);
+                 pixels = c[0] * c[1] - g.width * g.height;
+                 scale = pixels >= 0 ? pixels : -pixels * 4;
+                 if (best === null || aspect < bestAspect || (aspect == bestAspect && scale < bestScale)) {{
+                     best = c[2];
+                     bestAspect = aspect;
+                     bestScale = scale;
+                 }}
+             }}
+             return best;
+         }}
+         function screenOf(e) {{
+             if (e[0] !== null) return e[0];
+             if (e[1] !== null) return screenForConnector(e[1]);
+             return null;
+         }}
+         var Activities = activities();
+         for (a = 0; a < Activities.length; a++) {{
+             var Desktops = desktopsForActivity(Activities[a]);
+             for (i = 0; i < Desktops.length; i++) {{
+                 d = Desktops[i];
+                 var chosen = null;
+                 var best = -1;
+                 for (j = 0; j < entries.length; j++) {{
+                     e = entries[j];
+                     s = screenOf(e);
+                     if (e[0] !== null || e[1] !== null) {{
+                         if (s != d.screen) continue;
+                     }}
+                     if (e[2] !== null && e[2] != Activities[a]) continue;
+                     score = (s !== null ? 2 : 0) + (e[2] !== null ? 1 : 0);
+                     if (score > best) {{
+                         best = score;
+                         chosen = e;
+                     }}
+                 }}
+                 if (chosen === null) continue;
+                 d.wallpaperPlugin = chosen[3];
+                 d.currentConfigGroup = Array('Wallpaper', chosen[3], 'General');
+                 if (chosen[3] == 'org.kde.slideshow') {{
+                     d.writeConfig('SlidePaths', chosen[4]);
+                     d.writeConfig('SlideInterval', chosen[5]);
+                 }} else if (typeof chosen[4] == 'string') {{
+                     d.writeConfig('Image', chosen[4]);
+                 }} else {{
+                     d.writeConfig('Image', bestImage(chosen[4], d.screen));
+                 }}
+             }}
          }}",
-        wallpaper_uri
-    );
+        entries.join(", ")
+    )
+}
+
+/// A resolved and validated wallpaper, ready to be sent to plasmashell
+#[derive(Debug, Clone)]
+pub struct Prepared {
+    script: String,
+    /// Single images worth preloading. Which image of a package plasmashell shows depends on
+    /// the screen, so package images are not read ahead.
+    images: Vec<PathBuf>,
+}
 
+impl Prepared {
+    /// Read the images once so plasmashell loads them from the page cache when switching.
+    pub fn preload(&self) {
+        for image in self.images.iter() {
+            match fs::read(image) {
+                Ok(bytes) => log::debug!(
+                    "Preloaded {} ({} bytes)",
+                    image.to_string_lossy(),
+                    bytes.len()
+                ),
+                Err(e) => log::warn!("Failed to preload {}: {}", image.to_string_lossy(), e),
+            }
+        }
+    }
+}
+
+/// Resolve and validate the wallpaper for `theme` and build the plasmashell script.
+pub fn prepare(wallpaper: &Wallpaper, theme: &Theme) -> Result<Prepared, Error> {
+    log::debug!("Preparing wallpaper: {:?}", wallpaper);
+
+    let mut entries = targets(wallpaper, theme);
+    if entries.is_empty() {
+        log::error!("No wallpaper to apply for {:?}", wallpaper);
+        return Err(Error::new(ErrorKind::NotFound, "No wallpaper to apply"));
+    }
+
+    // Refuse to hand plasmashell anything it cannot show
+    let mut images = Vec::new();
+    for (_, source) in entries.iter_mut() {
+        let checked = match source {
+            Source::Image(path) => {
+                images.push(path.clone());
+                validate(path)
+            }
+            Source::Package { name, candidates } => {
+                candidates.retain(|c| match validate(&c.path) {
+                    Ok(_) => true,
+                    Err(e) => {
+                        log::warn!("Skipping package image: {}", e);
+                        false
+                    }
+                });
+                match candidates.is_empty() {
+                    true => Err(Error::new(
+                        ErrorKind::NotFound,
+                        format!("Wallpaper package {} has no usable images", name),
+                    )),
+                    false => Ok(()),
+                }
+            }
+            Source::Slideshow { directory, .. } if !directory.is_dir() => Err(Error::new(
+                ErrorKind::NotFound,
+                format!(
+                    "Wallpaper directory not found: {}",
+                    directory.to_string_lossy()
+                ),
+            )),
+            Source::Slideshow { .. } => Ok(()),
+        };
+        if let Err(e) = checked {
+            log::error!("Invalid wallpaper: {}", e);
+            return Err(e);
+        }
+    }
+
+    // JavaScript script sent to plasmashell via D-Bus
+    Ok(Prepared {
+        script: build_script(&entries),
+        images,
+    })
+}
+
+/// Send a prepared wallpaper to plasmashell.
+pub fn apply(prepared: &Prepared) -> Result<Output, Error> {
     log::debug!("Sending wallpaper configuration via D-Bus to plasmashell");
 
     // Call D-Bus
-    match send_dbus_script(&script) {
+    match send_dbus_script(&prepared.script) {
         Ok(_) => {
-            log::info!(
-                "Wallpaper applied successfully: {}",
-                wallpaper.to_string_lossy()
-            );
+            log::info!("Wallpaper applied successfully");
             Ok(Output {
                 status: std::process::ExitStatus::from_raw(0),
                 stdout: Vec::new(),
@@ -50,6 +532,11 @@ pub fn set(wallpaper: &PathBuf) -> Result<Output, Error> {
     }
 }
 
+pub fn set(wallpaper: &Wallpaper, theme: &Theme) -> Result<Output, Error> {
+    log::info!("Applying wallpaper: {:?}", wallpaper);
+    apply(&prepare(wallpaper, theme)?)
+}
+
 // Helper to send the D-Bus message
 fn send_dbus_script(script: &str) -> ZbusResult<()> {
     log::debug!("Connecting to D-Bus session");
@@ -67,6 +554,30 @@ fn send_dbus_script(script: &str) -> ZbusResult<()> {
     Ok(())
 }
 
+#[cfg(test)] // only build for tests
+fn percent_decode(value: &str) -> Vec<u8> {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], escaped) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    decoded
+}
+
 #[cfg(test)] // only build for tests
 fn get_current_wallpaper() -> Option<PathBuf> {
     let config_path: PathBuf =
@@ -85,7 +596,9 @@ fn get_current_wallpaper() -> Option<PathBuf> {
             let image_path = line.trim_start_matches("Image=").trim();
             // Remove "file://" prefix if present
             let local_path = image_path.strip_prefix("file://").unwrap_or(image_path);
-            return Some(PathBuf::from(local_path.to_string()));
+            return Some(PathBuf::from(OsStr::from_bytes(&percent_decode(
+                local_path,
+            ))));
         }
     }
     None
@@ -96,16 +609,201 @@ mod tests {
     use super::*;
     use crate::config::Config;
 
+    use rstest::rstest;
+    use std::collections::BTreeMap;
+
+    #[rstest]
+    #[case("*", None, None)]
+    #[case("0", Some(Screen::Index(0)), None)]
+    #[case("DP-1", Some(Screen::Connector(String::from("DP-1"))), None)]
+    #[case(
+        "5ba6b6a1-8f41-4b4d-a8d6-0c0a6c3d5e1f",
+        None,
+        Some(String::from("5ba6b6a1-8f41-4b4d-a8d6-0c0a6c3d5e1f"))
+    )]
+    #[case(
+        "HDMI-A-1@5ba6b6a1-8f41-4b4d-a8d6-0c0a6c3d5e1f",
+        Some(Screen::Connector(String::from("HDMI-A-1"))),
+        Some(String::from("5ba6b6a1-8f41-4b4d-a8d6-0c0a6c3d5e1f"))
+    )]
+    fn test_parse_target(
+        #[case] key: &str,
+        #[case] screen: Option<Screen>,
+        #[case] activity: Option<String>,
+    ) {
+        assert_eq!(parse_target(key), Target { screen, activity });
+    }
+
+    #[test]
+    fn test_build_script_per_desktop() {
+        let wallpaper = Wallpaper::PerDesktop(BTreeMap::from([
+            (String::from("*"), PathBuf::from("/w/default.png")),
+            (String::from("1"), PathBuf::from("/w/second.png")),
+            (String::from("DP-1"), PathBuf::from("/w/dp1.png")),
+        ]));
+
+        let script = build_script(&targets(&wallpaper, &Theme::Dark));
+
+        assert!(script.contains("[null, null, null, 'org.kde.image', 'file:///w/default.png', 0]"));
+        assert!(script.contains("[1, null, null, 'org.kde.image', 'file:///w/second.png', 0]"));
+        assert!(script.contains("[null, 'DP-1', null, 'org.kde.image', 'file:///w/dp1.png', 0]"));
+        assert!(script.contains("desktopsForActivity(Activities[a])"));
+    }
+
+    #[rstest]
+    #[case("/w/a.png", "'/w/a.png'")]
+    #[case("it's", "'it\\'s'")]
+    #[case("a\\b\"c", "'a\\\\b\\\"c'")]
+    #[case("line\nbreak\u{2028}", "'line\\nbreak\\u2028'")]
+    fn test_js_string(#[case] value: &str, #[case] expected: &str) {
+        assert_eq!(js_string(value), expected);
+    }
+
+    #[rstest]
+    #[case("/w/a.png", "file:///w/a.png")]
+    #[case(
+        "/w/Bamboo at Night/it's #1.png",
+        "file:///w/Bamboo%20at%20Night/it%27s%20%231.png"
+    )]
+    #[case("/w/100%/été.jpg", "file:///w/100%25/%C3%A9t%C3%A9.jpg")]
+    fn test_file_uri(#[case] path: &str, #[case] expected: &str) {
+        assert_eq!(file_uri(Path::new(path)), expected);
+    }
+
+    #[test]
+    fn test_percent_decode_roundtrip() {
+        let path = Path::new("/w/Bamboo at Night/été #1.png");
+        let uri = file_uri(path);
+        let decoded = percent_decode(uri.strip_prefix("file://").unwrap());
+        assert_eq!(Path::new(OsStr::from_bytes(&decoded)), path);
+    }
+
+    #[test]
+    fn test_build_script_escapes_paths() {
+        let wallpaper = Wallpaper::Single(PathBuf::from("/w/x'); evil('.png"));
+
+        let script = build_script(&targets(&wallpaper, &Theme::Dark));
+
+        assert!(script.contains("'file:///w/x%27%29%3B%20evil%28%27.png'"));
+        assert!(!script.contains("evil('"));
+    }
+
+    #[test]
+    fn test_validate() {
+        let directory = std::env::temp_dir().join("kswitch_test_wallpaper_validate");
+        fs::create_dir_all(&directory).unwrap();
+
+        let missing = directory.join("missing.png");
+        assert_eq!(validate(&missing).unwrap_err().kind(), ErrorKind::NotFound);
+
+        let not_an_image = directory.join("fake.png");
+        fs::write(&not_an_image, "not a png").unwrap();
+        assert_eq!(
+            validate(&not_an_image).unwrap_err().kind(),
+            ErrorKind::InvalidData
+        );
+
+        let image = directory.join("one pixel.png");
+        image::RgbImage::new(1, 1).save(&image).unwrap();
+        assert!(validate(&image).is_ok());
+
+        let _ = fs::remove_dir_all(&directory);
+    }
+
+    #[test]
+    fn test_build_script_package() {
+        let entries = vec![(
+            parse_target("*"),
+            Source::Package {
+                name: String::from("Bamboo"),
+                candidates: vec![
+                    Candidate {
+                        width: 1920,
+                        height: 1080,
+                        path: PathBuf::from("/w/Bamboo/contents/images/1920x1080.png"),
+                    },
+                    Candidate {
+                        width: 5120,
+                        height: 2880,
+                        path: PathBuf::from("/w/Bamboo/contents/images/5120x2880.png"),
+                    },
+                ],
+            },
+        )];
+
+        let script = build_script(&entries);
+
+        assert!(script.contains("[null, null, null, 'org.kde.image', [[1920, 1080, 'file:///w/Bamboo/contents/images/1920x1080.png'], [5120, 2880, 'file:///w/Bamboo/contents/images/5120x2880.png']], 0]"));
+        assert!(script.contains("bestImage(chosen[4], d.screen)"));
+        // Desktops without a screen (-1) must not divide by an empty geometry
+        assert!(script.contains("var g = screen >= 0 ? screenGeometry(screen) : null;"));
+        assert!(script.contains("return largestImage(candidates);"));
+    }
+
+    #[test]
+    fn test_build_script_slideshow() {
+        let wallpaper = Wallpaper::Directory(WallpaperDirectory {
+            directory: PathBuf::from("/w/night"),
+            mode: SelectionMode::Slideshow,
+            interval: 300,
+        });
+
+        let script = build_script(&targets(&wallpaper, &Theme::Dark));
+
+        assert!(script.contains("[null, null, null, 'org.kde.slideshow', '/w/night', 300]"));
+    }
+
+    #[rstest]
+    #[case(SelectionMode::RoundRobin, None, 7, "/w/a.png")]
+    #[case(SelectionMode::RoundRobin, Some("/w/a.png"), 7, "/w/b.png")]
+    #[case(SelectionMode::RoundRobin, Some("/w/c.png"), 7, "/w/a.png")]
+    #[case(SelectionMode::RoundRobin, Some("/w/gone.png"), 7, "/w/a.png")]
+    #[case(SelectionMode::Random, None, 4, "/w/b.png")]
+    #[case(SelectionMode::Random, Some("/w/b.png"), 4, "/w/c.png")]
+    fn test_pick(
+        #[case] mode: SelectionMode,
+        #[case] last: Option<&str>,
+        #[case] seed: u64,
+        #[case] expected: &str,
+    ) {
+        let images = vec![
+            PathBuf::from("/w/a.png"),
+            PathBuf::from("/w/b.png"),
+            PathBuf::from("/w/c.png"),
+        ];
+        let last = last.map(PathBuf::from);
+
+        let picked = pick(&images, &mode, last.as_ref(), seed);
+
+        assert_eq!(picked, Some(PathBuf::from(expected)));
+    }
+
+    #[test]
+    fn test_list_images() {
+        let directory = std::env::temp_dir().join("kswitch_test_wallpaper_dir");
+        fs::create_dir_all(&directory).unwrap();
+        for name in ["b.JPG", "a.png", "notes.txt"] {
+            fs::write(directory.join(name), "").unwrap();
+        }
+
+        assert_eq!(
+            list_images(&directory),
+            vec![directory.join("a.png"), directory.join("b.JPG")]
+        );
+
+        let _ = fs::remove_dir_all(&directory);
+    }
+
     #[test]
     fn test_set_wallpaper() {
         let config = Config::default();
 
         let current = get_current_wallpaper().expect("Failed to get current wallpaper");
 
-        let out = if current == config.light.wallpaper {
-            set(&config.dark.wallpaper)
+        let out = if config.light.wallpaper == Wallpaper::Single(current) {
+            set(&config.dark.wallpaper, &Theme::Dark)
         } else {
-            set(&config.light.wallpaper)
+            set(&config.light.wallpaper, &Theme::Light)
         };
 
         assert!(out.is_ok());
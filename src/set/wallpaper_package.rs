@@ -0,0 +1,171 @@
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::theme::Theme;
+
+/// The parts of a wallpaper package's `metadata.json` kswitch cares about
+#[derive(Deserialize, Debug, Default)]
+struct Metadata {
+    #[serde(default, rename = "KPlugin")]
+    k_plugin: PluginInfo,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(rename_all = "PascalCase")]
+struct PluginInfo {
+    #[serde(default)]
+    id: Option<String>,
+    #[serde(default)]
+    name: Option<String>,
+}
+
+/// An image shipped by a wallpaper package, with its resolution
+#[derive(Debug, PartialEq, Clone)]
+pub struct Candidate {
+    pub width: u32,
+    pub height: u32,
+    pub path: PathBuf,
+}
+
+/// Wallpaper packages (e.g. `/usr/share/wallpapers/Bamboo`) have a `metadata.json` and
+/// their images under `contents/images`.
+pub fn is_package(path: &Path) -> bool {
+    path.join("metadata.json").is_file() && path.join("contents/images").is_dir()
+}
+
+/// Human readable package name from `metadata.json`, falling back to the directory name.
+pub fn name(package: &Path) -> String {
+    let metadata: Metadata = fs::read_to_string(package.join("metadata.json"))
+        .ok()
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default();
+
+    metadata
+        .k_plugin
+        .name
+        .or(metadata.k_plugin.id)
+        .unwrap_or_else(|| package.to_string_lossy().to_string())
+}
+
+/// Parse the resolution from package image names such as `5120x2880.png`.
+fn resolution_from_name(path: &Path) -> Option<(u32, u32)> {
+    let stem = path.file_stem()?.to_str()?;
+    let (width, height) = stem.split_once('x')?;
+    Some((width.parse().ok()?, height.parse().ok()?))
+}
+
+/// Images directory for the theme: dual light/dark packages keep the dark variants in
+/// `contents/images_dark`.
+fn images_dir(package: &Path, theme: &Theme) -> PathBuf {
+    let dark = package.join("contents/images_dark");
+    match theme {
+        Theme::Dark if dark.is_dir() => dark,
+        _ => package.join("contents/images"),
+    }
+}
+
+/// All images of a package for the given theme, with their resolutions.
+pub fn candidates(package: &Path, theme: &Theme) -> Vec<Candidate> {
+    let dir = images_dir(package, theme);
+    log::debug!(
+        "Reading wallpaper package images from {}",
+        dir.to_string_lossy()
+    );
+
+    let mut candidates: Vec<Candidate> = match fs::read_dir(&dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.is_file())
+            .filter_map(|path| {
+                // Fall back to the image header for images not named after their size
+                let (width, height) =
+                    resolution_from_name(&path).or_else(|| image::image_dimensions(&path).ok())?;
+                Some(Candidate {
+                    width,
+                    height,
+                    path,
+                })
+            })
+            .collect(),
+        Err(e) => {
+            log::error!(
+                "Failed to read wallpaper package images {}: {}",
+                dir.to_string_lossy(),
+                e
+            );
+            Vec::new()
+        }
+    };
+    candidates.sort_by_key(|c| (c.width, c.height));
+    candidates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env::temp_dir;
+
+    fn make_package(root: &Path, dark: bool) {
+        fs::create_dir_all(root.join("contents/images")).unwrap();
+        fs::write(
+            root.join("metadata.json"),
+            r#"{"KPlugin": {"Id": "Bamboo", "Name": "Bamboo", "Authors": []}}"#,
+        )
+        .unwrap();
+        for name in ["1920x1080.png", "5120x2880.png"] {
+            fs::write(root.join("contents/images").join(name), "").unwrap();
+        }
+        if dark {
+            fs::create_dir_all(root.join("contents/images_dark")).unwrap();
+            fs::write(root.join("contents/images_dark/3840x2160.png"), "").unwrap();
+        }
+    }
+
+    #[test]
+    fn test_package_candidates() {
+        let root = temp_dir().join("kswitch_test_wallpaper_package");
+        make_package(&root, false);
+
+        assert!(is_package(&root));
+        assert!(!is_package(&root.join("contents")));
+        assert_eq!(name(&root), "Bamboo");
+
+        let candidates = candidates(&root, &Theme::Dark);
+        assert_eq!(
+            candidates,
+            vec![
+                Candidate {
+                    width: 1920,
+                    height: 1080,
+                    path: root.join("contents/images/1920x1080.png"),
+                },
+                Candidate {
+                    width: 5120,
+                    height: 2880,
+                    path: root.join("contents/images/5120x2880.png"),
+                },
+            ]
+        );
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_dual_package_candidates() {
+        let root = temp_dir().join("kswitch_test_wallpaper_dual_package");
+        make_package(&root, true);
+
+        let dark = candidates(&root, &Theme::Dark);
+        assert_eq!(dark.len(), 1);
+        assert_eq!(
+            dark[0].path,
+            root.join("contents/images_dark/3840x2160.png")
+        );
+
+        let light = candidates(&root, &Theme::Light);
+        assert_eq!(light.len(), 2);
+
+        let _ = fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,56 @@
+use std::error::Error;
+use std::path::PathBuf;
+
+use zbus::blocking::{Connection, Proxy};
+
+use crate::kconfig;
+use crate::theme::WindowDecoration;
+
+fn kwinrc_path() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or(PathBuf::from("~/.config"))
+        .join("kwinrc")
+}
+
+/// Ask KWin to reload its configuration, which picks up the new decoration.
+fn reconfigure_kwin() -> zbus::Result<()> {
+    log::debug!("Asking KWin to reconfigure");
+    let connection = Connection::session()?;
+    let proxy = Proxy::new(&connection, "org.kde.KWin", "/KWin", "org.kde.KWin")?;
+    proxy.call_method("reconfigure", &())?;
+    Ok(())
+}
+
+pub fn set(decoration: &WindowDecoration) -> Result<(), Box<dyn Error>> {
+    log::info!(
+        "Applying window decoration: {} ({})",
+        decoration.theme,
+        decoration.library
+    );
+
+    let path = kwinrc_path();
+    kconfig::write_entry(
+        &path,
+        &["org.kde.kdecoration2"],
+        "library",
+        &decoration.library,
+    )?;
+    kconfig::write_entry(&path, &["org.kde.kdecoration2"], "theme", &decoration.theme)?;
+
+    match reconfigure_kwin() {
+        Ok(_) => {
+            log::info!(
+                "Window decoration applied successfully: {}",
+                decoration.theme
+            );
+            Ok(())
+        }
+        Err(e) => {
+            log::warn!(
+                "Window decoration written but KWin reconfigure failed: {}",
+                e
+            );
+            Err(Box::new(e))
+        }
+    }
+}
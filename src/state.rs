@@ -1,6 +1,8 @@
 use crate::theme::Theme;
+use chrono::NaiveDateTime;
 use log;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::error::Error;
 use std::fs;
 use std::path::PathBuf;
@@ -8,6 +10,115 @@ use std::path::PathBuf;
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ThemeState {
     pub current_theme: Theme,
+    /// Set by `kswitch schedule pause`: the daemon leaves the theme alone
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub schedule_paused: bool,
+    /// When a pause ends by itself, if it does
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub paused_until: Option<NaiveDateTime>,
+    /// Last wallpaper picked from each wallpaper directory, keyed by directory
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub wallpaper_picks: BTreeMap<String, PathBuf>,
+    /// Theme set with `--for` or `--until`, which the schedule leaves alone until it expires
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub manual_override: Option<Override>,
+}
+
+impl Default for ThemeState {
+    fn default() -> Self {
+        ThemeState {
+            current_theme: Theme::Light,
+            schedule_paused: false,
+            paused_until: None,
+            wallpaper_picks: BTreeMap::new(),
+            manual_override: None,
+        }
+    }
+}
+
+impl ThemeState {
+    /// The override still in effect at `now`, if any
+    pub fn active_override(&self, now: &NaiveDateTime) -> Option<&Override> {
+        self.manual_override
+            .as_ref()
+            .filter(|manual_override| manual_override.is_active(now))
+    }
+
+    /// Whether the schedule is paused at `now`
+    pub fn is_paused(&self, now: &NaiveDateTime) -> bool {
+        self.schedule_paused && self.paused_until.is_none_or(|until| *now < until)
+    }
+}
+
+/// A theme pinned until a point in local time
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Override {
+    pub theme: Theme,
+    pub until: NaiveDateTime,
+}
+
+impl Override {
+    pub fn is_active(&self, now: &NaiveDateTime) -> bool {
+        *now < self.until
+    }
+}
+
+/// Marks a switch in progress until dropped, so the daemon can tell the writes of a switch
+/// from changes made outside kswitch. Each process gets its own marker, named by its pid.
+pub struct SwitchLock {
+    path: PathBuf,
+}
+
+impl SwitchLock {
+    fn dir() -> PathBuf {
+        dirs::runtime_dir()
+            .or_else(dirs::config_dir)
+            .unwrap_or(PathBuf::from("/tmp"))
+            .join("kswitch/switching")
+    }
+
+    pub fn acquire() -> Self {
+        let dir = Self::dir();
+        let path = dir.join(std::process::id().to_string());
+        if let Err(e) = fs::create_dir_all(&dir).and_then(|_| fs::write(&path, "")) {
+            log::warn!(
+                "Failed to mark switch in progress at {}: {}",
+                path.to_string_lossy(),
+                e
+            );
+        }
+        SwitchLock { path }
+    }
+
+    /// Whether a process is switching right now. Markers left by processes that died are
+    /// removed.
+    pub fn held() -> bool {
+        let Ok(entries) = fs::read_dir(Self::dir()) else {
+            return false;
+        };
+        let mut held = false;
+        for entry in entries.flatten() {
+            let alive = entry
+                .file_name()
+                .to_str()
+                .and_then(|name| name.parse::<u32>().ok())
+                .is_some_and(|pid| PathBuf::from(format!("/proc/{}", pid)).exists());
+            match alive {
+                true => held = true,
+                false => {
+                    log::debug!("Removing stale switch marker {:?}", entry.file_name());
+                    let _ = fs::remove_file(entry.path());
+                }
+            }
+        }
+        held
+    }
+}
+
+impl Drop for SwitchLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -27,9 +138,16 @@ impl StateManager {
     pub fn save(&self, theme: &Theme) -> Result<(), Box<dyn Error>> {
         log::debug!("Saving theme state: {}", theme.to_string());
 
-        let state = ThemeState {
-            current_theme: theme.clone(),
-        };
+        self.update(|state| state.current_theme = theme.clone())
+    }
+
+    /// Load the state, apply `f` and write it back, keeping all other fields.
+    pub fn update<F: FnOnce(&mut ThemeState)>(&self, f: F) -> Result<(), Box<dyn Error>> {
+        let mut state = self.load().unwrap_or_else(|e| {
+            log::warn!("Failed to load existing theme state, starting fresh: {}", e);
+            ThemeState::default()
+        });
+        f(&mut state);
 
         let toml_string = toml::to_string(&state)?;
 
@@ -52,9 +170,7 @@ impl StateManager {
 
         if !self.state_path.exists() {
             log::debug!("State file does not exist, returning Light theme as default");
-            return Ok(ThemeState {
-                current_theme: Theme::Light,
-            });
+            return Ok(ThemeState::default());
         }
 
         let contents = fs::read_to_string(&self.state_path)?;
@@ -124,4 +240,95 @@ mod tests {
         // Cleanup
         let _ = fs::remove_file(&state_manager.state_path);
     }
+
+    #[test]
+    fn test_state_manager_save_keeps_other_fields() {
+        let state_manager = StateManager {
+            state_path: std::env::temp_dir().join("kswitch_test_state_update.toml"),
+        };
+        let _ = fs::remove_file(&state_manager.state_path);
+
+        state_manager
+            .update(|state| {
+                state
+                    .wallpaper_picks
+                    .insert(String::from("/w/night"), PathBuf::from("/w/night/b.png"));
+            })
+            .unwrap();
+        state_manager.save(&Theme::Dark).unwrap();
+
+        let state = state_manager.load().unwrap();
+        assert_eq!(state.current_theme, Theme::Dark);
+        assert_eq!(
+            state.wallpaper_picks.get("/w/night"),
+            Some(&PathBuf::from("/w/night/b.png"))
+        );
+
+        let _ = fs::remove_file(&state_manager.state_path);
+    }
+
+    #[test]
+    fn test_state_manager_manual_override() {
+        let state_manager = StateManager {
+            state_path: std::env::temp_dir().join("kswitch_test_state_override.toml"),
+        };
+        let _ = fs::remove_file(&state_manager.state_path);
+        let until = NaiveDateTime::parse_from_str("2025-06-01 18:00", "%Y-%m-%d %H:%M").unwrap();
+
+        state_manager
+            .update(|state| {
+                state.manual_override = Some(Override {
+                    theme: Theme::Dark,
+                    until,
+                })
+            })
+            .unwrap();
+        state_manager.save(&Theme::Dark).unwrap();
+
+        let state = state_manager.load().unwrap();
+        let before = until - chrono::TimeDelta::minutes(1);
+        assert_eq!(
+            state.active_override(&before).map(|o| &o.theme),
+            Some(&Theme::Dark)
+        );
+        assert!(state.active_override(&until).is_none());
+
+        let _ = fs::remove_file(&state_manager.state_path);
+    }
+
+    #[test]
+    fn test_schedule_pause() {
+        let now = NaiveDateTime::parse_from_str("2025-06-01 12:00", "%Y-%m-%d %H:%M").unwrap();
+        let mut state = ThemeState::default();
+        assert!(!state.is_paused(&now));
+
+        state.schedule_paused = true;
+        assert!(state.is_paused(&now));
+
+        state.paused_until = Some(now + chrono::TimeDelta::hours(1));
+        assert!(state.is_paused(&now));
+        state.paused_until = Some(now);
+        assert!(!state.is_paused(&now));
+
+        let state: ThemeState = toml::from_str(&toml::to_string(&state).unwrap()).unwrap();
+        assert!(state.schedule_paused);
+        assert_eq!(state.paused_until, Some(now));
+    }
+
+    #[test]
+    fn test_switch_lock_markers() {
+        let dir = SwitchLock::dir();
+        fs::create_dir_all(&dir).unwrap();
+
+        // Our parent is alive, a pid beyond the kernel's limit never is
+        let alive = dir.join(std::os::unix::process::parent_id().to_string());
+        let stale = dir.join("4194305");
+        fs::write(&alive, "").unwrap();
+        fs::write(&stale, "").unwrap();
+
+        assert!(SwitchLock::held());
+        assert!(!stale.exists());
+
+        let _ = fs::remove_file(&alive);
+    }
 }
@@ -1,5 +1,6 @@
 use clap::Subcommand;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 use std::str::FromStr;
 
@@ -33,10 +34,158 @@ impl FromStr for Theme {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct Style {
-    pub wallpaper: PathBuf,
+    pub wallpaper: Wallpaper,
     pub color_scheme: String,
     pub desktop_theme: String,
     pub terminal_profile: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub icon_theme: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cursor_theme: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cursor_size: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gtk_theme: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub window_decoration: Option<WindowDecoration>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lockscreen_wallpaper: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub neovim: Option<Neovim>,
+    /// `workbench.colorTheme` for VS Code and VSCodium
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vscode_theme: Option<String>,
+    /// File sourced into every running tmux server
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tmux_theme: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bat_theme: Option<String>,
+    /// `delta.syntax-theme` in the global git config
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delta_theme: Option<String>,
+    /// Value of fzf's `--color` option, e.g. `"light"` or `"fg:#4d4d4c,bg:#eeeeee"`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fzf_colors: Option<String>,
+    /// Color schemes of KDE applications that keep their own, keyed by application (`okular`)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub app_color_schemes: BTreeMap<String, AppColorScheme>,
+}
+
+/// Wallpaper for the desktop containments.
+///
+/// Either a single image or directory for every desktop, a directory with an explicit
+/// selection mode, or a table keyed by screen index (`"0"`), connector name (`"DP-1"`),
+/// activity id, or `"<screen>@<activity>"`, with `"*"` as a fallback for desktops no other
+/// key matches. A plain directory path picks a random image from it.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(untagged, try_from = "UncheckedWallpaper")]
+pub enum Wallpaper {
+    Single(PathBuf),
+    Directory(WallpaperDirectory),
+    PerDesktop(BTreeMap<String, PathBuf>),
+}
+
+/// [`Wallpaper`] as the untagged variants read it, before [`Wallpaper::try_from`] checks it
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum UncheckedWallpaper {
+    Single(PathBuf),
+    Directory(WallpaperDirectory),
+    PerDesktop(BTreeMap<String, PathBuf>),
+}
+
+/// Fields of a [`WallpaperDirectory`], which are never names of screens or activities
+const DIRECTORY_FIELDS: [&str; 3] = ["directory", "mode", "interval"];
+
+impl TryFrom<UncheckedWallpaper> for Wallpaper {
+    type Error = String;
+
+    /// Reject a mistyped directory table, which would otherwise pass as a per-desktop table
+    /// keyed by `directory` and `mode`.
+    fn try_from(wallpaper: UncheckedWallpaper) -> Result<Self, Self::Error> {
+        match wallpaper {
+            UncheckedWallpaper::Single(path) => Ok(Wallpaper::Single(path)),
+            UncheckedWallpaper::Directory(directory) => Ok(Wallpaper::Directory(directory)),
+            UncheckedWallpaper::PerDesktop(map) => {
+                if !DIRECTORY_FIELDS
+                    .iter()
+                    .any(|field| map.contains_key(*field))
+                {
+                    return Ok(Wallpaper::PerDesktop(map));
+                }
+                // Read it as the directory it was meant to be, for the actual mistake
+                let error = toml::Value::try_from(&map)
+                    .map_err(|e| e.to_string())
+                    .and_then(|value| {
+                        value
+                            .try_into::<WallpaperDirectory>()
+                            .map_err(|e| e.to_string())
+                    })
+                    .err()
+                    .unwrap_or_default();
+                Err(format!("invalid wallpaper directory: {}", error.trim_end()))
+            }
+        }
+    }
+}
+
+/// How an image is chosen from a wallpaper directory on each switch
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum SelectionMode {
+    #[default]
+    Random,
+    RoundRobin,
+    /// Hand the directory to Plasma's `org.kde.slideshow` plugin
+    Slideshow,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct WallpaperDirectory {
+    pub directory: PathBuf,
+    #[serde(default)]
+    pub mode: SelectionMode,
+    /// Seconds between images in slideshow mode
+    #[serde(default = "default_slideshow_interval")]
+    pub interval: u64,
+}
+
+fn default_slideshow_interval() -> u64 {
+    600
+}
+
+/// KWin window decoration, as stored in `kwinrc [org.kde.kdecoration2]`.
+///
+/// Breeze uses `library = "org.kde.breeze"`, `theme = "Breeze"`; Aurorae themes use
+/// `library = "org.kde.kwin.aurorae"`, `theme = "__aurorae__svg__<name>"`.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct WindowDecoration {
+    pub library: String,
+    pub theme: String,
+}
+
+/// Running Neovim instances get `background` set to the theme, and optionally a colorscheme.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+pub struct Neovim {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub colorscheme: Option<String>,
+}
+
+/// Color scheme override of a single KDE application.
+///
+/// Either just the name of a color scheme for the application's window, or a table that can also
+/// set the editor color theme of KTextEditor based applications such as Kate, KWrite and KDevelop.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(untagged)]
+pub enum AppColorScheme {
+    Name(String),
+    Detailed {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        color_scheme: Option<String>,
+        /// KTextEditor color theme, e.g. `"Breeze Dark"`
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        editor_theme: Option<String>,
+    },
 }
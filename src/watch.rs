@@ -0,0 +1,143 @@
+use std::error::Error;
+use std::ffi::OsStr;
+use std::sync::mpsc::Sender;
+use std::thread;
+
+use inotify::{Inotify, WatchMask};
+use zbus::MatchRule;
+use zbus::blocking::{Connection, MessageIterator};
+use zbus::message::Type;
+use zbus::zvariant::OwnedValue;
+
+use crate::daemon::Request;
+use crate::get::external_theme::{kdeglobals_path, plasmarc_path};
+use crate::get::portal::{from_setting_changed, setting_changed_rule};
+
+/// Watch the directory holding kdeglobals and plasmarc for writes to either file.
+///
+/// KConfig saves by writing a temporary file and renaming it over the old one, so watching the
+/// files themselves would lose track of them after the first save.
+fn watch_files(requests: Sender<Request>) -> Result<(), Box<dyn Error>> {
+    let kdeglobals = kdeglobals_path();
+    let plasmarc = plasmarc_path();
+    let dir = kdeglobals
+        .parent()
+        .ok_or("kdeglobals has no parent directory")?;
+    let names: Vec<&OsStr> = [&kdeglobals, &plasmarc]
+        .iter()
+        .filter_map(|path| path.file_name())
+        .collect();
+
+    let mut inotify = Inotify::init()?;
+    inotify
+        .watches()
+        .add(dir, WatchMask::CLOSE_WRITE | WatchMask::MOVED_TO)?;
+    log::info!(
+        "Watching {} and {} for external changes",
+        kdeglobals.to_string_lossy(),
+        plasmarc.to_string_lossy()
+    );
+
+    let mut buffer = [0; 4096];
+    loop {
+        let events = inotify.read_events_blocking(&mut buffer)?;
+        let changed = events
+            .filter_map(|event| event.name)
+            .find(|name| names.contains(name));
+        if let Some(name) = changed {
+            log::debug!("{} changed", dir.join(name).to_string_lossy());
+            if requests.send(Request::External).is_err() {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Listen for `org.kde.KGlobalSettings.notifyChange`, which KDE emits when the palette, style
+/// or icons changed.
+fn watch_notify_change(requests: Sender<Request>) -> Result<(), Box<dyn Error>> {
+    let connection = Connection::session()?;
+    let rule = MatchRule::builder()
+        .msg_type(Type::Signal)
+        .path("/KGlobalSettings")?
+        .interface("org.kde.KGlobalSettings")?
+        .member("notifyChange")?
+        .build();
+    log::info!("Listening for KGlobalSettings notifyChange");
+
+    for message in MessageIterator::for_match_rule(rule, &connection, None)? {
+        match message {
+            Ok(_) => {
+                log::debug!("KGlobalSettings notifyChange received");
+                if requests.send(Request::External).is_err() {
+                    break;
+                }
+            }
+            Err(e) => log::warn!("Failed to receive notifyChange: {}", e),
+        }
+    }
+    Ok(())
+}
+
+/// Listen for the settings portal announcing a new `org.freedesktop.appearance color-scheme`.
+fn watch_portal(requests: Sender<Request>) -> Result<(), Box<dyn Error>> {
+    let connection = Connection::session()?;
+    log::info!("Following the settings portal color-scheme");
+
+    for message in MessageIterator::for_match_rule(setting_changed_rule()?, &connection, None)? {
+        let body = match message {
+            Ok(message) => message.body().deserialize::<(String, String, OwnedValue)>(),
+            Err(e) => {
+                log::warn!("Failed to receive SettingChanged: {}", e);
+                continue;
+            }
+        };
+        let preference = match body {
+            Ok((namespace, key, value)) => from_setting_changed(&namespace, &key, &value),
+            Err(e) => {
+                log::warn!("Malformed SettingChanged signal: {}", e);
+                continue;
+            }
+        };
+        if let Some(preference) = preference {
+            log::debug!("Portal color-scheme changed to {}", preference);
+            match preference.theme() {
+                Some(theme) => {
+                    if requests.send(Request::Portal(theme)).is_err() {
+                        break;
+                    }
+                }
+                None => log::debug!("Portal has no color-scheme preference, ignoring"),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Follow the portal color-scheme, reporting each change as [`Request::Portal`].
+pub fn spawn_portal(requests: &Sender<Request>) {
+    let portal = requests.clone();
+    thread::spawn(move || {
+        if let Err(e) = watch_portal(portal) {
+            log::error!("Stopped following the settings portal: {}", e);
+        }
+    });
+}
+
+/// Start watching for theme changes made outside kswitch, reporting each as
+/// [`Request::External`].
+pub fn spawn(requests: &Sender<Request>) {
+    let files = requests.clone();
+    thread::spawn(move || {
+        if let Err(e) = watch_files(files) {
+            log::error!("Stopped watching KDE config files: {}", e);
+        }
+    });
+
+    let signals = requests.clone();
+    thread::spawn(move || {
+        if let Err(e) = watch_notify_change(signals) {
+            log::error!("Stopped listening for notifyChange: {}", e);
+        }
+    });
+}